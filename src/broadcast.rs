@@ -0,0 +1,280 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{subscription, TrayIconEvent};
+
+/// What happens to the events received by a full [`TrayIconEventSubscriber`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OverflowPolicy {
+    /// Remove the oldest queued event to make room for the new one.
+    #[default]
+    DropOldest,
+    /// Discard the new event.
+    DropNewest,
+}
+
+/// Options of a [`TrayIconEventSubscriber`], see [`TrayIconEvent::subscribe_with`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct SubscribeOptions {
+    /// Maximum number of queued events, `None` for an unbounded queue.
+    pub capacity: Option<usize>,
+    /// What to do with events received when the queue is full.
+    pub overflow: OverflowPolicy,
+    /// Replace a queued [`TrayIconEvent::Move`] with a new one of the same tray icon
+    /// when nothing was queued in between, so a slow subscriber only sees the latest position.
+    pub coalesce_moves: bool,
+}
+
+impl SubscribeOptions {
+    /// Creates options for an unbounded queue without coalescing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of queued events.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Sets what to do with events received when the queue is full.
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets whether consecutive [`TrayIconEvent::Move`] events are coalesced.
+    pub fn with_coalesce_moves(mut self, coalesce_moves: bool) -> Self {
+        self.coalesce_moves = coalesce_moves;
+        self
+    }
+}
+
+#[derive(Default)]
+struct Events {
+    queue: VecDeque<TrayIconEvent>,
+    dropped: u64,
+}
+
+pub(crate) struct Queue {
+    options: SubscribeOptions,
+    events: Mutex<Events>,
+    available: Condvar,
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, Events> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn push(&self, event: TrayIconEvent) {
+        let mut events = self.lock();
+
+        if self.options.coalesce_moves && matches!(event, TrayIconEvent::Move { .. }) {
+            if let Some(last @ TrayIconEvent::Move { .. }) = events.queue.back_mut() {
+                if last.id() == event.id() {
+                    *last = event;
+                    return;
+                }
+            }
+        }
+
+        if self.options.capacity == Some(events.queue.len()) {
+            events.dropped += 1;
+            match self.options.overflow {
+                OverflowPolicy::DropOldest => {
+                    events.queue.pop_front();
+                }
+                OverflowPolicy::DropNewest => return,
+            }
+        }
+        // a zero capacity drops everything
+        if self.options.capacity != Some(0) {
+            events.queue.push_back(event);
+            self.available.notify_one();
+        }
+    }
+}
+
+/// A subscriber receiving every tray event in its own queue, see [`TrayIconEvent::subscribe`].
+///
+/// Events are queued from the moment the subscriber is created, regardless of
+/// [`TrayIconEvent::set_event_handler`] and of the other subscribers and receivers.
+/// The subscription ends when the subscriber is dropped.
+pub struct TrayIconEventSubscriber {
+    queue: Arc<Queue>,
+}
+
+impl TrayIconEventSubscriber {
+    pub(crate) fn new(options: SubscribeOptions) -> Self {
+        let queue = Arc::new(Queue {
+            options,
+            events: Default::default(),
+            available: Condvar::new(),
+        });
+        subscription::subscribe_broadcast(Arc::downgrade(&queue));
+        Self { queue }
+    }
+
+    /// Returns the oldest queued event, waiting for one if the queue is empty.
+    pub fn recv(&self) -> TrayIconEvent {
+        let mut events = self.queue.lock();
+        loop {
+            if let Some(event) = events.queue.pop_front() {
+                return event;
+            }
+            events = self
+                .queue
+                .available
+                .wait(events)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Returns the oldest queued event, waiting at most `timeout` for one.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<TrayIconEvent> {
+        let deadline = Instant::now() + timeout;
+        let mut events = self.queue.lock();
+        loop {
+            if let Some(event) = events.queue.pop_front() {
+                return Some(event);
+            }
+            let timeout = deadline.checked_duration_since(Instant::now())?;
+            events = self
+                .queue
+                .available
+                .wait_timeout(events, timeout)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Returns the oldest queued event, if any.
+    pub fn try_recv(&self) -> Option<TrayIconEvent> {
+        self.queue.lock().queue.pop_front()
+    }
+
+    /// Returns an iterator over the queued events, stopping when the queue is empty.
+    pub fn try_iter(&self) -> impl Iterator<Item = TrayIconEvent> + '_ {
+        std::iter::from_fn(|| self.try_recv())
+    }
+
+    /// Returns the number of queued events.
+    pub fn len(&self) -> usize {
+        self.queue.lock().queue.len()
+    }
+
+    /// Returns `true` if no event is queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of events lost because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.lock().dropped
+    }
+
+    /// Returns the options this subscriber was created with.
+    pub fn options(&self) -> SubscribeOptions {
+        self.queue.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dpi::PhysicalPosition, EventTimestamp, Rect, TrayIconId};
+
+    fn moved(id: &str, x: f64) -> TrayIconEvent {
+        TrayIconEvent::Move {
+            id: TrayIconId::new(id),
+            position: PhysicalPosition::new(x, 0.),
+            rect: Rect::default(),
+            timestamp: EventTimestamp::now(),
+        }
+    }
+
+    fn queue(options: SubscribeOptions) -> TrayIconEventSubscriber {
+        TrayIconEventSubscriber {
+            queue: Arc::new(Queue {
+                options,
+                events: Default::default(),
+                available: Condvar::new(),
+            }),
+        }
+    }
+
+    fn positions(subscriber: &TrayIconEventSubscriber) -> Vec<f64> {
+        subscriber
+            .try_iter()
+            .map(|event| match event {
+                TrayIconEvent::Move { position, .. } => position.x,
+                _ => panic!("unexpected event {event:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drops_on_overflow() {
+        let oldest = queue(SubscribeOptions::new().with_capacity(2));
+        let newest = queue(
+            SubscribeOptions::new()
+                .with_capacity(2)
+                .with_overflow(OverflowPolicy::DropNewest),
+        );
+        for x in [1., 2., 3., 4.] {
+            oldest.queue.push(moved("a", x));
+            newest.queue.push(moved("a", x));
+        }
+
+        assert_eq!(oldest.dropped(), 2);
+        assert_eq!(positions(&oldest), [3., 4.]);
+        assert_eq!(newest.dropped(), 2);
+        assert_eq!(positions(&newest), [1., 2.]);
+        assert!(oldest.is_empty());
+        assert!(oldest.recv_timeout(Duration::from_millis(10)).is_none());
+    }
+
+    #[test]
+    fn coalesces_moves() {
+        let subscriber = queue(SubscribeOptions::new().with_coalesce_moves(true));
+        for event in [
+            moved("a", 1.),
+            moved("a", 2.),
+            moved("b", 3.),
+            moved("b", 4.),
+            moved("a", 5.),
+            moved("a", 6.),
+        ] {
+            subscriber.queue.push(event);
+        }
+
+        assert_eq!(subscriber.len(), 3);
+        assert_eq!(subscriber.dropped(), 0);
+        assert_eq!(positions(&subscriber), [2., 4., 6.]);
+    }
+
+    #[test]
+    fn broadcasts_to_every_subscriber() {
+        let id = TrayIconId::new("broadcast");
+        let first = TrayIconEventSubscriber::new(SubscribeOptions::new());
+        let second = TrayIconEventSubscriber::new(SubscribeOptions::new());
+
+        let sender = std::thread::spawn(|| subscription::dispatch(&moved("broadcast", 1.)));
+        for subscriber in [&first, &second] {
+            let event = std::iter::repeat_with(|| subscriber.recv_timeout(Duration::from_secs(5)))
+                .map(|event| event.expect("no broadcast event"))
+                .find(|event| event.id() == &id)
+                .unwrap();
+            assert!(matches!(event, TrayIconEvent::Move { .. }));
+        }
+        sender.join().unwrap();
+    }
+}
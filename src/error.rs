@@ -10,11 +10,19 @@ use thiserror::Error;
 pub enum Error {
     #[error(transparent)]
     OsError(#[from] std::io::Error),
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[cfg(any(
+        all(target_os = "linux", feature = "libappindicator", not(feature = "sni")),
+        target_os = "macos"
+    ))]
     #[error(transparent)]
     PngEncodingError(#[from] png::EncodingError),
+    #[cfg(all(target_os = "linux", feature = "sni"))]
+    #[error(transparent)]
+    DBusError(#[from] zbus::Error),
     #[error("not on the main thread")]
     NotMainThread,
+    #[error("no tray host is available to show the tray icon")]
+    NoTrayHost,
 }
 
 /// Convenient type alias of Result type for tray-icon.
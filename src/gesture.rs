@@ -0,0 +1,742 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Recognizes double clicks and long presses from the raw click events, the same way
+//! on all platforms.
+//!
+//! Feed the tray events to a [`GestureRecognizer`] and call [`GestureRecognizer::poll`]
+//! once [`GestureRecognizer::next_deadline`] is reached, to get delayed single clicks
+//! and long presses even when no other event comes in.
+//!
+//! ```no_run
+//! use std::time::{Duration, Instant};
+//! use tray_icon::{
+//!     gesture::{Gesture, GestureConfig, GestureRecognizer},
+//!     TrayIconEvent,
+//! };
+//!
+//! let mut recognizer = GestureRecognizer::new(
+//!     GestureConfig::new()
+//!         .with_delay_single_click(true)
+//!         .with_long_press(Duration::from_millis(600)),
+//! );
+//!
+//! loop {
+//!     let timeout = recognizer
+//!         .next_deadline()
+//!         .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+//!         .unwrap_or(Duration::from_secs(1));
+//!     let gestures = match TrayIconEvent::receiver().recv_timeout(timeout) {
+//!         Ok(event) => recognizer.handle(&event),
+//!         Err(_) => recognizer.poll(Instant::now()),
+//!     };
+//!     for gesture in gestures {
+//!         match gesture {
+//!             Gesture::Click { .. } => println!("single click"),
+//!             Gesture::DoubleClick { .. } => println!("double click"),
+//!             Gesture::LongPress { .. } => println!("long press"),
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    dpi::PhysicalPosition, EventTimestamp, Modifiers, MouseButton, MouseButtonState, Rect,
+    TrayIconEvent, TrayIconId,
+};
+
+/// A gesture recognized by a [`GestureRecognizer`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+#[non_exhaustive]
+pub enum Gesture {
+    /// The tray icon was clicked once.
+    ///
+    /// With [`GestureConfig::delay_single_click`], only emitted once the double click
+    /// interval has passed without a second click.
+    Click {
+        /// Id of the tray icon which was clicked.
+        id: TrayIconId,
+        /// Physical position of the release.
+        position: PhysicalPosition<f64>,
+        /// Position and size of the tray icon.
+        rect: Rect,
+        /// Mouse button that was clicked.
+        button: MouseButton,
+        /// Keyboard modifiers held when the button was pressed.
+        modifiers: Modifiers,
+        /// When the button was released.
+        timestamp: EventTimestamp,
+    },
+    /// The tray icon was clicked twice within the double click interval.
+    ///
+    /// Emitted on the second press, the second click is not reported as a [`Gesture::Click`].
+    DoubleClick {
+        /// Id of the tray icon which was double clicked.
+        id: TrayIconId,
+        /// Physical position of the second press.
+        position: PhysicalPosition<f64>,
+        /// Position and size of the tray icon.
+        rect: Rect,
+        /// Mouse button that was double clicked.
+        button: MouseButton,
+        /// Keyboard modifiers held during the second press.
+        modifiers: Modifiers,
+        /// When the button was pressed the second time.
+        timestamp: EventTimestamp,
+    },
+    /// A button was held down on the tray icon for [`GestureConfig::long_press`].
+    ///
+    /// The release that follows is not reported as a [`Gesture::Click`].
+    LongPress {
+        /// Id of the tray icon which was pressed.
+        id: TrayIconId,
+        /// Physical position of the press.
+        position: PhysicalPosition<f64>,
+        /// Position and size of the tray icon.
+        rect: Rect,
+        /// Mouse button that was held down.
+        button: MouseButton,
+        /// Keyboard modifiers held when the button was pressed.
+        modifiers: Modifiers,
+        /// When the button was pressed.
+        timestamp: EventTimestamp,
+    },
+}
+
+/// Configuration of a [`GestureRecognizer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GestureConfig {
+    /// Maximum time between two presses to make a double click,
+    /// defaults to [`double_click_interval`](crate::double_click_interval).
+    pub double_click_interval: Duration,
+    /// Hold back single clicks until the double click interval has passed, so the
+    /// first click of a double click is never reported as a [`Gesture::Click`].
+    pub delay_single_click: bool,
+    /// How long a button must be held down to make a long press, `None` to
+    /// report these as clicks.
+    pub long_press: Option<Duration>,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            double_click_interval: crate::double_click_interval(),
+            delay_single_click: false,
+            long_press: None,
+        }
+    }
+}
+
+impl GestureConfig {
+    /// Creates a configuration using the system double click interval,
+    /// without delaying single clicks nor detecting long presses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum time between two presses to make a double click.
+    pub fn with_double_click_interval(mut self, interval: Duration) -> Self {
+        self.double_click_interval = interval;
+        self
+    }
+
+    /// Sets whether single clicks are held back until the double click interval has passed.
+    pub fn with_delay_single_click(mut self, delay: bool) -> Self {
+        self.delay_single_click = delay;
+        self
+    }
+
+    /// Detects long presses of at least `duration`.
+    pub fn with_long_press(mut self, duration: Duration) -> Self {
+        self.long_press = Some(duration);
+        self
+    }
+}
+
+/// A press or release of a button on a tray icon.
+#[derive(Debug, Clone)]
+struct Press {
+    id: TrayIconId,
+    position: PhysicalPosition<f64>,
+    rect: Rect,
+    button: MouseButton,
+    modifiers: Modifiers,
+    timestamp: EventTimestamp,
+}
+
+impl Press {
+    fn click(self) -> Gesture {
+        Gesture::Click {
+            id: self.id,
+            position: self.position,
+            rect: self.rect,
+            button: self.button,
+            modifiers: self.modifiers,
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn double_click(self) -> Gesture {
+        Gesture::DoubleClick {
+            id: self.id,
+            position: self.position,
+            rect: self.rect,
+            button: self.button,
+            modifiers: self.modifiers,
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn long_press(self) -> Gesture {
+        Gesture::LongPress {
+            id: self.id,
+            position: self.position,
+            rect: self.rect,
+            button: self.button,
+            modifiers: self.modifiers,
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn time(&self) -> Instant {
+        self.timestamp.instant
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    /// The button is down, `second` when this press already made a double click,
+    /// such a press is never a long press.
+    Pressed {
+        press: Press,
+        second: bool,
+        long_pressed: bool,
+    },
+    /// The button was clicked once and may be clicked again, `release` is the
+    /// click to emit if the double click interval passes with
+    /// [`GestureConfig::delay_single_click`].
+    Released {
+        first_press: Instant,
+        release: Press,
+    },
+}
+
+/// Turns raw [`TrayIconEvent::Click`] events into [`Gesture`]s.
+///
+/// Each button of each tray icon is tracked on its own. The time of the events
+/// is read from their [`EventTimestamp`].
+#[derive(Debug)]
+pub struct GestureRecognizer {
+    config: GestureConfig,
+    states: HashMap<(TrayIconId, MouseButton), State>,
+}
+
+impl GestureRecognizer {
+    /// Creates a recognizer with the given configuration.
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Returns the configuration of this recognizer.
+    pub fn config(&self) -> &GestureConfig {
+        &self.config
+    }
+
+    /// Handles a tray event and returns the gestures it completes.
+    ///
+    /// Gestures whose deadline passed before `event` are returned first, as with
+    /// [`GestureRecognizer::poll`]. Events other than clicks are ignored, native
+    /// [`TrayIconEvent::DoubleClick`] events are treated as presses.
+    pub fn handle(&mut self, event: &TrayIconEvent) -> Vec<Gesture> {
+        let mut gestures = self.poll(event.timestamp().instant);
+
+        let (press, button_state) = match event.clone() {
+            TrayIconEvent::Click {
+                id,
+                position,
+                rect,
+                button,
+                button_state,
+                modifiers,
+                timestamp,
+            } => (
+                Press {
+                    id,
+                    position,
+                    rect,
+                    button,
+                    modifiers,
+                    timestamp,
+                },
+                button_state,
+            ),
+            TrayIconEvent::DoubleClick {
+                id,
+                position,
+                rect,
+                button,
+                modifiers,
+                timestamp,
+            } => (
+                Press {
+                    id,
+                    position,
+                    rect,
+                    button,
+                    modifiers,
+                    timestamp,
+                },
+                MouseButtonState::Down,
+            ),
+            _ => return gestures,
+        };
+
+        let key = (press.id.clone(), press.button);
+        match button_state {
+            MouseButtonState::Down => match self.states.remove(&key) {
+                Some(State::Released { first_press, .. })
+                    if press.time().saturating_duration_since(first_press)
+                        < self.config.double_click_interval =>
+                {
+                    self.states.insert(
+                        key,
+                        State::Pressed {
+                            press: press.clone(),
+                            second: true,
+                            long_pressed: false,
+                        },
+                    );
+                    gestures.push(press.double_click());
+                }
+                _ => {
+                    self.states.insert(
+                        key,
+                        State::Pressed {
+                            press,
+                            second: false,
+                            long_pressed: false,
+                        },
+                    );
+                }
+            },
+            MouseButtonState::Up => {
+                let Some(State::Pressed {
+                    press: down,
+                    second,
+                    long_pressed,
+                }) = self.states.remove(&key)
+                else {
+                    return gestures;
+                };
+
+                let held = press.time().saturating_duration_since(down.time());
+                // a long press reported by poll, or the second click of a double click
+                if long_pressed || second {
+                    return gestures;
+                }
+                if self.config.long_press.is_some_and(|long| held >= long) {
+                    gestures.push(down.long_press());
+                } else {
+                    let release = Press {
+                        modifiers: down.modifiers,
+                        ..press
+                    };
+                    if !self.config.delay_single_click {
+                        gestures.push(release.clone().click());
+                    }
+                    self.states.insert(
+                        key,
+                        State::Released {
+                            first_press: down.time(),
+                            release,
+                        },
+                    );
+                }
+            }
+        }
+
+        gestures
+    }
+
+    /// Returns the gestures whose deadline passed at `now`: delayed single clicks
+    /// and long presses of buttons still held down.
+    pub fn poll(&mut self, now: Instant) -> Vec<Gesture> {
+        let config = self.config;
+        let mut expired = Vec::new();
+        let mut gestures = Vec::new();
+
+        for (key, state) in self.states.iter_mut() {
+            match state {
+                // the second press of a double click is never a long press
+                State::Pressed { second: true, .. } => {}
+                State::Pressed {
+                    press,
+                    long_pressed,
+                    ..
+                } => {
+                    if let Some(long) = config.long_press {
+                        if !*long_pressed && now.saturating_duration_since(press.time()) >= long {
+                            *long_pressed = true;
+                            gestures.push(press.clone().long_press());
+                        }
+                    }
+                }
+                State::Released {
+                    first_press,
+                    release,
+                } => {
+                    if now.saturating_duration_since(*first_press) >= config.double_click_interval {
+                        if config.delay_single_click {
+                            gestures.push(release.clone().click());
+                        }
+                        expired.push(key.clone());
+                    }
+                }
+            }
+        }
+
+        for key in expired {
+            self.states.remove(&key);
+        }
+
+        gestures.sort_by_key(gesture_time);
+        gestures
+    }
+
+    /// Returns when [`GestureRecognizer::poll`] should be called next to emit
+    /// delayed single clicks or long presses, if any is pending.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.states
+            .values()
+            .filter_map(|state| match state {
+                State::Pressed {
+                    press,
+                    second: false,
+                    long_pressed: false,
+                } => self.config.long_press.map(|long| press.time() + long),
+                State::Pressed { .. } => None,
+                State::Released { first_press, .. } => self
+                    .config
+                    .delay_single_click
+                    .then(|| *first_press + self.config.double_click_interval),
+            })
+            .min()
+    }
+}
+
+fn gesture_time(gesture: &Gesture) -> Instant {
+    match gesture {
+        Gesture::Click { timestamp, .. }
+        | Gesture::DoubleClick { timestamp, .. }
+        | Gesture::LongPress { timestamp, .. } => timestamp.instant,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(400);
+
+    /// Builds synthetic events relative to a fixed start time.
+    struct Events {
+        start: Instant,
+    }
+
+    impl Events {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+            }
+        }
+
+        fn at(&self, millis: u64) -> Instant {
+            self.start + Duration::from_millis(millis)
+        }
+
+        fn click(
+            &self,
+            millis: u64,
+            id: &str,
+            button: MouseButton,
+            button_state: MouseButtonState,
+        ) -> TrayIconEvent {
+            TrayIconEvent::Click {
+                id: TrayIconId::new(id),
+                position: PhysicalPosition::new(millis as f64, 0.),
+                rect: Rect::default(),
+                button,
+                button_state,
+                modifiers: Modifiers::default(),
+                timestamp: EventTimestamp {
+                    instant: self.at(millis),
+                    platform_time: None,
+                    sequence: 0,
+                },
+            }
+        }
+
+        fn down(&self, millis: u64) -> TrayIconEvent {
+            self.click(millis, "a", MouseButton::Left, MouseButtonState::Down)
+        }
+
+        fn up(&self, millis: u64) -> TrayIconEvent {
+            self.click(millis, "a", MouseButton::Left, MouseButtonState::Up)
+        }
+    }
+
+    /// Summarizes gestures as `(kind, id, time since start in ms)`.
+    fn summary(events: &Events, gestures: Vec<Gesture>) -> Vec<(&'static str, String, u64)> {
+        gestures
+            .into_iter()
+            .map(|gesture| {
+                let time = gesture_time(&gesture)
+                    .duration_since(events.start)
+                    .as_millis() as u64;
+                let (kind, id) = match gesture {
+                    Gesture::Click { id, .. } => ("click", id),
+                    Gesture::DoubleClick { id, .. } => ("double", id),
+                    Gesture::LongPress { id, .. } => ("long", id),
+                };
+                (kind, id.0, time)
+            })
+            .collect()
+    }
+
+    fn run(
+        recognizer: &mut GestureRecognizer,
+        events: &Events,
+        input: Vec<TrayIconEvent>,
+        end: u64,
+    ) -> Vec<(&'static str, String, u64)> {
+        let mut gestures = Vec::new();
+        for event in &input {
+            gestures.extend(recognizer.handle(event));
+        }
+        gestures.extend(recognizer.poll(events.at(end)));
+        summary(events, gestures)
+    }
+
+    fn config() -> GestureConfig {
+        GestureConfig {
+            double_click_interval: INTERVAL,
+            delay_single_click: false,
+            long_press: None,
+        }
+    }
+
+    #[test]
+    fn synthesizes_double_clicks() {
+        let e = Events::new();
+        let mut recognizer = GestureRecognizer::new(config());
+
+        let input = vec![e.down(0), e.up(50), e.down(200), e.up(250)];
+        assert_eq!(
+            run(&mut recognizer, &e, input, 1000),
+            [("click", "a".into(), 50), ("double", "a".into(), 200)]
+        );
+
+        // too slow
+        let input = vec![e.down(2000), e.up(2050), e.down(2500), e.up(2550)];
+        assert_eq!(
+            run(&mut recognizer, &e, input, 3000),
+            [("click", "a".into(), 2050), ("click", "a".into(), 2550)]
+        );
+
+        // a third click starts over
+        let input = vec![
+            e.down(4000),
+            e.up(4050),
+            e.down(4100),
+            e.up(4150),
+            e.down(4200),
+            e.up(4250),
+        ];
+        assert_eq!(
+            run(&mut recognizer, &e, input, 5000),
+            [
+                ("click", "a".into(), 4050),
+                ("double", "a".into(), 4100),
+                ("click", "a".into(), 4250),
+            ]
+        );
+        assert_eq!(recognizer.next_deadline(), None);
+    }
+
+    #[test]
+    fn tracks_icons_and_buttons_separately() {
+        let e = Events::new();
+        let mut recognizer = GestureRecognizer::new(config());
+
+        let input = vec![
+            e.down(0),
+            e.up(50),
+            e.click(100, "b", MouseButton::Left, MouseButtonState::Down),
+            e.click(150, "b", MouseButton::Left, MouseButtonState::Up),
+            e.click(200, "a", MouseButton::Right, MouseButtonState::Down),
+            e.click(250, "a", MouseButton::Right, MouseButtonState::Up),
+            e.down(300),
+            e.up(350),
+        ];
+        assert_eq!(
+            run(&mut recognizer, &e, input, 1000),
+            [
+                ("click", "a".into(), 50),
+                ("click", "b".into(), 150),
+                ("click", "a".into(), 250),
+                ("double", "a".into(), 300),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_native_double_clicks() {
+        let e = Events::new();
+        let mut recognizer = GestureRecognizer::new(config());
+
+        // Windows replaces the second press with a double click
+        let native = TrayIconEvent::DoubleClick {
+            id: TrayIconId::new("a"),
+            position: PhysicalPosition::default(),
+            rect: Rect::default(),
+            button: MouseButton::Left,
+            modifiers: Modifiers::default(),
+            timestamp: EventTimestamp {
+                instant: e.at(200),
+                platform_time: None,
+                sequence: 0,
+            },
+        };
+        let input = vec![e.down(0), e.up(50), native, e.up(250)];
+        assert_eq!(
+            run(&mut recognizer, &e, input, 1000),
+            [("click", "a".into(), 50), ("double", "a".into(), 200)]
+        );
+    }
+
+    #[test]
+    fn delays_single_clicks() {
+        let e = Events::new();
+        let mut recognizer = GestureRecognizer::new(config().with_delay_single_click(true));
+
+        assert!(recognizer.handle(&e.down(0)).is_empty());
+        assert!(recognizer.handle(&e.up(50)).is_empty());
+        assert_eq!(recognizer.next_deadline(), Some(e.at(400)));
+        assert!(recognizer.poll(e.at(399)).is_empty());
+        assert_eq!(
+            summary(&e, recognizer.poll(recognizer.next_deadline().unwrap())),
+            [("click", "a".into(), 50)]
+        );
+        assert_eq!(recognizer.next_deadline(), None);
+
+        // the first click of a double click is never reported
+        let input = vec![e.down(1000), e.up(1050), e.down(1200), e.up(1250)];
+        assert_eq!(
+            run(&mut recognizer, &e, input, 2000),
+            [("double", "a".into(), 1200)]
+        );
+
+        // the delayed click is flushed by a later event
+        let input = [e.down(3000), e.up(3050), e.down(3500), e.up(3550)];
+        assert_eq!(
+            summary(
+                &e,
+                input
+                    .iter()
+                    .flat_map(|event| recognizer.handle(event))
+                    .collect()
+            ),
+            [("click", "a".into(), 3050)]
+        );
+    }
+
+    #[test]
+    fn detects_long_presses() {
+        let e = Events::new();
+        let mut recognizer =
+            GestureRecognizer::new(config().with_long_press(Duration::from_millis(600)));
+
+        // reported by poll while the button is held down
+        assert!(recognizer.handle(&e.down(0)).is_empty());
+        assert_eq!(recognizer.next_deadline(), Some(e.at(600)));
+        assert!(recognizer.poll(e.at(500)).is_empty());
+        assert_eq!(
+            summary(&e, recognizer.poll(e.at(650))),
+            [("long", "a".into(), 0)]
+        );
+        assert!(recognizer.poll(e.at(700)).is_empty());
+        assert!(recognizer.handle(&e.up(800)).is_empty());
+
+        // reported on release when poll wasn't called in time
+        let input = [e.down(2000), e.up(2700)];
+        assert_eq!(
+            summary(
+                &e,
+                input
+                    .iter()
+                    .flat_map(|event| recognizer.handle(event))
+                    .collect()
+            ),
+            [("long", "a".into(), 2000)]
+        );
+
+        // short presses are still clicks
+        let input = vec![e.down(4000), e.up(4100)];
+        assert_eq!(
+            run(&mut recognizer, &e, input, 5000),
+            [("click", "a".into(), 4100)]
+        );
+    }
+
+    #[test]
+    fn ignores_long_second_presses() {
+        let e = Events::new();
+        let mut recognizer =
+            GestureRecognizer::new(config().with_long_press(Duration::from_millis(600)));
+
+        // polled while the second press is held down
+        let input = [e.down(0), e.up(50), e.down(200)];
+        let mut gestures: Vec<_> = input
+            .iter()
+            .flat_map(|event| recognizer.handle(event))
+            .collect();
+        assert_eq!(recognizer.next_deadline(), None);
+        gestures.extend(recognizer.poll(e.at(1000)));
+        gestures.extend(recognizer.handle(&e.up(1100)));
+        assert_eq!(
+            summary(&e, gestures),
+            [("click", "a".into(), 50), ("double", "a".into(), 200)]
+        );
+
+        // not polled in time
+        let input = vec![e.down(2000), e.up(2050), e.down(2200), e.up(3100)];
+        assert_eq!(
+            run(&mut recognizer, &e, input, 4000),
+            [("click", "a".into(), 2050), ("double", "a".into(), 2200)]
+        );
+    }
+
+    #[test]
+    fn expires_double_clicks_at_the_deadline() {
+        let e = Events::new();
+        let mut recognizer = GestureRecognizer::new(config());
+
+        // a second press right at the end of the interval is a new click
+        let input = vec![e.down(0), e.up(50), e.down(400), e.up(450)];
+        assert_eq!(
+            run(&mut recognizer, &e, input, 1000),
+            [("click", "a".into(), 50), ("click", "a".into(), 450)]
+        );
+    }
+}
@@ -103,6 +103,64 @@ mod constructors {
         }
     }
 
+    impl RgbaIcon {
+        /// Draws `overlay` over the bottom right quarter of this icon.
+        ///
+        /// The overlay is scaled with nearest neighbour sampling and blended
+        /// with the usual "source over" operator.
+        pub(crate) fn with_overlay(&self, overlay: &RgbaIcon) -> RgbaIcon {
+            let mut rgba = self.rgba.clone();
+            // nothing to draw on or to draw
+            if self.width == 0 || self.height == 0 || overlay.width == 0 || overlay.height == 0 {
+                return RgbaIcon {
+                    rgba,
+                    width: self.width,
+                    height: self.height,
+                };
+            }
+
+            let overlay_width = (self.width / 2).max(1);
+            let overlay_height = (self.height / 2).max(1);
+            let x_offset = self.width - overlay_width;
+            let y_offset = self.height - overlay_height;
+
+            for y in 0..overlay_height {
+                for x in 0..overlay_width {
+                    let src_x = x * overlay.width / overlay_width;
+                    let src_y = y * overlay.height / overlay_height;
+                    let src = ((src_y * overlay.width + src_x) as usize) * PIXEL_SIZE;
+                    let dst = (((y + y_offset) * self.width + x + x_offset) as usize) * PIXEL_SIZE;
+
+                    let src = &overlay.rgba[src..src + PIXEL_SIZE];
+                    blend(&mut rgba[dst..dst + PIXEL_SIZE], src);
+                }
+            }
+
+            RgbaIcon {
+                rgba,
+                width: self.width,
+                height: self.height,
+            }
+        }
+    }
+
+    /// Blends the non-premultiplied `src` pixel over `dst`.
+    fn blend(dst: &mut [u8], src: &[u8]) {
+        let src_alpha = src[3] as u32;
+        let dst_alpha = dst[3] as u32 * (255 - src_alpha);
+        // alpha of the result, scaled by 255
+        let alpha = src_alpha * 255 + dst_alpha;
+        if alpha == 0 {
+            dst.fill(0);
+            return;
+        }
+
+        for i in 0..3 {
+            dst[i] = ((src[i] as u32 * src_alpha * 255 + dst[i] as u32 * dst_alpha) / alpha) as u8;
+        }
+        dst[3] = ((alpha + 127) / 255) as u8;
+    }
+
     impl NoIcon {
         pub fn from_rgba(rgba: Vec<u8>, width: u32, height: u32) -> Result<Self, BadIcon> {
             // Create the rgba icon anyway to validate the input
@@ -135,6 +193,26 @@ impl Icon {
         })
     }
 
+    /// Draws `overlay` over `icon`, for platforms without native overlay support.
+    ///
+    /// Icons that can't be read back as RGBA, e.g. loaded from a file on Windows,
+    /// are shown without the overlay.
+    #[allow(unused)]
+    pub(crate) fn with_overlay(icon: Option<&Icon>, overlay: Option<&Icon>) -> Option<Icon> {
+        let icon = icon?;
+        let composited = overlay.and_then(|overlay| {
+            let base = icon.inner.to_rgba()?;
+            let overlay = overlay.inner.to_rgba()?;
+            let RgbaIcon {
+                rgba,
+                width,
+                height,
+            } = base.with_overlay(&overlay);
+            Icon::from_rgba(rgba, width, height).ok()
+        });
+        Some(composited.unwrap_or_else(|| icon.clone()))
+    }
+
     /// Create an icon from a file path.
     ///
     /// Specify `size` to load a specific icon size from the file, or `None` to load the default
@@ -182,3 +260,62 @@ impl Icon {
         Icon { inner: win_icon }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icon(pixel: [u8; 4], width: u32, height: u32) -> RgbaIcon {
+        let rgba = pixel.repeat((width * height) as usize);
+        RgbaIcon::from_rgba(rgba, width, height).unwrap()
+    }
+
+    #[test]
+    fn overlay_covers_bottom_right_quarter() {
+        let base = icon([255, 0, 0, 255], 4, 4);
+        let overlay = icon([0, 0, 255, 255], 1, 1);
+
+        let composited = base.with_overlay(&overlay);
+        for (i, pixel) in composited.rgba.chunks(PIXEL_SIZE).enumerate() {
+            let (x, y) = (i % 4, i / 4);
+            let expected = if x >= 2 && y >= 2 {
+                [0, 0, 255, 255]
+            } else {
+                [255, 0, 0, 255]
+            };
+            assert_eq!(pixel, expected, "pixel at {x},{y}");
+        }
+    }
+
+    #[test]
+    fn overlay_blends_alpha() {
+        let base = icon([255, 0, 0, 255], 1, 1);
+
+        let composited = base.with_overlay(&icon([0, 0, 255, 0], 1, 1));
+        assert_eq!(composited.rgba, [255, 0, 0, 255]);
+
+        let composited = base.with_overlay(&icon([0, 0, 255, 128], 1, 1));
+        assert_eq!(composited.rgba, [127, 0, 128, 255]);
+
+        let transparent = icon([0, 0, 0, 0], 1, 1);
+        let composited = transparent.with_overlay(&icon([0, 255, 0, 128], 1, 1));
+        assert_eq!(composited.rgba, [0, 255, 0, 128]);
+    }
+
+    #[test]
+    fn overlay_ignores_empty_icons() {
+        let base = icon([255, 0, 0, 255], 2, 2);
+        for empty in [icon([0; 4], 0, 0), icon([0; 4], 0, 4), icon([0; 4], 4, 0)] {
+            let composited = base.with_overlay(&empty);
+            assert_eq!((composited.width, composited.height), (2, 2));
+            assert_eq!(composited.rgba, base.rgba);
+
+            let composited = empty.with_overlay(&base);
+            assert_eq!(
+                (composited.width, composited.height),
+                (empty.width, empty.height)
+            );
+            assert!(composited.rgba.is_empty());
+        }
+    }
+}
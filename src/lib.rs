@@ -21,6 +21,21 @@
 //!
 //! On Linux, `gtk`, `libxdo` is used to make the predfined `Copy`, `Cut`, `Paste` and `SelectAll` menu items work and `libappindicator` or `libayatnat-appindicator` are used to create the tray icon, so make sure to install them on your system.
 //!
+//! When the `sni` feature is enabled, the tray icon is exported directly over D-Bus as a
+//! [StatusNotifierItem](https://www.freedesktop.org/wiki/Specifications/StatusNotifierItem/)
+//! and `libappindicator` is not used, disable the default `libappindicator` feature to not link
+//! to it at all. Icons are sent inline in several sizes instead of being
+//! written to temporary PNG files. The tray menu is then exported as `com.canonical.dbusmenu`
+//! and rendered by the tray host, menu items are still backed by gtk so a gtk event loop is
+//! required for [`MenuEvent`](crate::menu::MenuEvent)s to be emitted. Tray icons are
+//! registered again whenever the `org.kde.StatusNotifierWatcher` comes back, e.g. after
+//! the tray host restarted.
+//!
+//! The `xembed` feature, which implies `sni`, adds a fallback for X11 desktops without a
+//! `org.kde.StatusNotifierWatcher`: the icon is then docked with the
+//! [System Tray Protocol](https://specifications.freedesktop.org/systemtray-spec/) into
+//! an XEmbed window drawn by tray-icon itself and the menu is shown on right click.
+//!
 //! #### Arch Linux / Manjaro:
 //!
 //! ```sh
@@ -75,6 +90,22 @@
 //! }
 //! ```
 //!
+//! The events of [`TrayIconEvent::receiver`] are shared between all its users, each event is only
+//! received once. Use [`TrayIconEvent::subscribe`] to get all the events in a separate queue.
+//!
+//! To only get the events of one tray icon, use [`TrayIcon::event_receiver`] or [`TrayIcon::on_event`].
+//!
+//! ```no_run
+//! # let tray_icon = tray_icon::TrayIconBuilder::new().build().unwrap();
+//! let receiver = tray_icon.event_receiver();
+//! if let Ok(event) = receiver.try_recv() {
+//!     println!("{:?}", event);
+//! }
+//! ```
+//!
+//! Click events are reported as the platform sends them, which differs between platforms.
+//! Use a [`gesture::GestureRecognizer`] to get double clicks and long presses consistently.
+//!
 //! You can also listen for the menu events using [`MenuEvent::receiver`](crate::menu::MenuEvent::receiver) to get events for the tray context menu.
 //!
 //! ```no_run
@@ -124,20 +155,36 @@ use std::{
     cell::RefCell,
     path::{Path, PathBuf},
     rc::Rc,
+    sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use counter::Counter;
 use crossbeam_channel::{unbounded, Receiver, Sender};
-use once_cell::sync::{Lazy, OnceCell};
+use once_cell::sync::Lazy;
+use subscription::Subscriptions;
+#[cfg(feature = "async")]
+pub use subscription::TrayIconEventStream;
 
+mod broadcast;
 mod counter;
 mod error;
+pub mod gesture;
 mod icon;
 mod platform_impl;
+pub mod positioner;
+mod rect;
+mod subscription;
+mod timestamp;
+mod tooltip;
 mod tray_icon_id;
 
+pub use self::broadcast::{OverflowPolicy, SubscribeOptions, TrayIconEventSubscriber};
 pub use self::error::*;
 pub use self::icon::{BadIcon, Icon};
+pub use self::rect::{LogicalRect, Rect};
+pub use self::timestamp::EventTimestamp;
+pub use self::tooltip::Tooltip;
 pub use self::tray_icon_id::TrayIconId;
 
 /// Re-export of [muda](::muda) crate and used for tray context menu.
@@ -154,14 +201,10 @@ pub struct TrayIconAttributes {
     ///
     /// ## Platform-specific:
     ///
-    /// - **Linux:** Unsupported.
-    pub tooltip: Option<String>,
+    /// - **Linux:** Requires the `sni` feature, unsupported otherwise.
+    pub tooltip: Option<Tooltip>,
 
     /// Tray menu
-    ///
-    /// ## Platform-specific:
-    ///
-    /// - **Linux**: once a menu is set, it cannot be removed.
     pub menu: Option<Box<dyn menu::ContextMenu>>,
 
     /// Tray icon
@@ -173,6 +216,8 @@ pub struct TrayIconAttributes {
     pub icon: Option<Icon>,
 
     /// Tray icon temp dir path. **Linux only**.
+    ///
+    /// Unused with the `sni` feature, where icons are sent over D-Bus.
     pub temp_dir_path: Option<PathBuf>,
 
     /// Use the icon as a [template](https://developer.apple.com/documentation/appkit/nsimage/1520017-template?language=objc). **macOS only**.
@@ -192,6 +237,26 @@ pub struct TrayIconAttributes {
     ///   on the user's panel.  This may not be shown in all visualizations.
     /// - **Windows:** Unsupported.
     pub title: Option<String>,
+
+    /// Application id used by the tray host to tell icons apart, e.g. to remember
+    /// their order or whether they are hidden. **Linux only**.
+    ///
+    /// Defaults to `{executable name}-{tray icon id}`, so it only needs to be set
+    /// when the tray icon id is not stable across runs.
+    pub app_id: Option<String>,
+
+    /// Category used by the tray host to group icons. **Linux only**.
+    pub category: TrayCategory,
+
+    /// Whether creating the tray icon fails with [`Error::NoTrayHost`] when
+    /// [`is_tray_available`] returns `false`, default is `false`.
+    pub require_tray_host: bool,
+
+    /// Whether the overlay set with [`TrayIcon::set_overlay_icon`] is drawn into
+    /// the icon instead of being sent to the tray host separately, for hosts that
+    /// don't show overlays. Default is `false`. **Linux with the `sni` feature only**,
+    /// the overlay is always drawn into the icon otherwise.
+    pub composite_overlay: bool,
 }
 
 impl Default for TrayIconAttributes {
@@ -204,10 +269,30 @@ impl Default for TrayIconAttributes {
             icon_is_template: false,
             menu_on_left_click: true,
             title: None,
+            app_id: None,
+            category: TrayCategory::default(),
+            require_tray_host: false,
+            composite_overlay: false,
         }
     }
 }
 
+impl TrayIconAttributes {
+    /// The [`app_id`](Self::app_id) or its default value for the tray icon `id`.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn app_id_or_default(&self, id: &TrayIconId) -> String {
+        if let Some(app_id) = &self.app_id {
+            return app_id.clone();
+        }
+
+        let exe = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.file_stem()?.to_str().map(ToString::to_string))
+            .unwrap_or_else(|| "tray-icon".to_string());
+        format!("{exe}-{}", id.as_ref())
+    }
+}
+
 /// [`TrayIcon`] builder struct and associated methods.
 #[derive(Default)]
 pub struct TrayIconBuilder {
@@ -233,10 +318,6 @@ impl TrayIconBuilder {
     }
 
     /// Set the a menu for this tray icon.
-    ///
-    /// ## Platform-specific:
-    ///
-    /// - **Linux**: once a menu is set, it cannot be removed or replaced but you can change its content.
     pub fn with_menu(mut self, menu: Box<dyn menu::ContextMenu>) -> Self {
         self.attrs.menu = Some(menu);
         self
@@ -255,11 +336,14 @@ impl TrayIconBuilder {
 
     /// Set a tooltip for this tray icon.
     ///
+    /// Accepts a plain string or a [`Tooltip`] with a body and an icon.
+    ///
     /// ## Platform-specific:
     ///
-    /// - **Linux:** Unsupported.
-    pub fn with_tooltip<S: AsRef<str>>(mut self, s: S) -> Self {
-        self.attrs.tooltip = Some(s.as_ref().to_string());
+    /// - **Linux:** Requires the `sni` feature, unsupported otherwise.
+    /// - **Windows / macOS:** See [`Tooltip`] for how it is shown.
+    pub fn with_tooltip<T: Into<Tooltip>>(mut self, tooltip: T) -> Self {
+        self.attrs.tooltip = Some(tooltip.into());
         self
     }
 
@@ -281,7 +365,10 @@ impl TrayIconBuilder {
     /// Set tray icon temp dir path. **Linux only**.
     ///
     /// On Linux, we need to write the icon to the disk and usually it will
-    /// be `$XDG_RUNTIME_DIR/tray-icon` or `$TEMP/tray-icon`.
+    /// be `$XDG_RUNTIME_DIR/tray-icon` or `$TEMP/tray-icon`. Icons are written
+    /// to a subdirectory named after the process id, see [`cleanup_temp_icons`].
+    ///
+    /// Unused with the `sni` feature, where icons are sent over D-Bus.
     pub fn with_temp_dir_path<P: AsRef<Path>>(mut self, s: P) -> Self {
         self.attrs.temp_dir_path = Some(s.as_ref().to_path_buf());
         self
@@ -299,6 +386,35 @@ impl TrayIconBuilder {
         self
     }
 
+    /// Set the application id used by the tray host to tell icons apart. **Linux only**.
+    ///
+    /// See [`TrayIconAttributes::app_id`] for the default value.
+    pub fn with_app_id<S: AsRef<str>>(mut self, app_id: S) -> Self {
+        self.attrs.app_id = Some(app_id.as_ref().to_string());
+        self
+    }
+
+    /// Set the category used by the tray host to group icons. **Linux only**.
+    pub fn with_category(mut self, category: TrayCategory) -> Self {
+        self.attrs.category = category;
+        self
+    }
+
+    /// Draw the overlay into the icon instead of sending it to the tray host separately.
+    ///
+    /// See [`TrayIconAttributes::composite_overlay`].
+    pub fn with_composite_overlay(mut self, enable: bool) -> Self {
+        self.attrs.composite_overlay = enable;
+        self
+    }
+
+    /// Whether [`build`](Self::build) fails with [`Error::NoTrayHost`] when
+    /// [`is_tray_available`] returns `false` instead of creating an icon nobody sees.
+    pub fn with_require_tray_host(mut self, require: bool) -> Self {
+        self.attrs.require_tray_host = require;
+        self
+    }
+
     /// Access the unique id that will be assigned to the tray icon
     /// this builder will create.
     pub fn id(&self) -> &TrayIconId {
@@ -318,6 +434,7 @@ impl TrayIconBuilder {
 pub struct TrayIcon {
     id: TrayIconId,
     tray: Rc<RefCell<platform_impl::TrayIcon>>,
+    subscriptions: Rc<Subscriptions>,
 }
 
 impl TrayIcon {
@@ -328,26 +445,24 @@ impl TrayIcon {
     /// - **Linux:** Sometimes the icon won't be visible unless a menu is set.
     ///   Setting an empty [`Menu`](crate::menu::Menu) is enough.
     pub fn new(attrs: TrayIconAttributes) -> Result<Self> {
-        let id = TrayIconId(COUNTER.next().to_string());
-        Ok(Self {
-            tray: Rc::new(RefCell::new(platform_impl::TrayIcon::new(
-                id.clone(),
-                attrs,
-            )?)),
-            id,
-        })
+        Self::with_id(TrayIconId(COUNTER.next().to_string()), attrs)
     }
 
     /// Builds and adds a new tray icon to the system tray with the specified Id.
     ///
     /// See [`TrayIcon::new`] for more info.
     pub fn with_id<I: Into<TrayIconId>>(id: I, attrs: TrayIconAttributes) -> Result<Self> {
+        if attrs.require_tray_host && !is_tray_available() {
+            return Err(Error::NoTrayHost);
+        }
+
         let id = id.into();
         Ok(Self {
             tray: Rc::new(RefCell::new(platform_impl::TrayIcon::new(
                 id.clone(),
                 attrs,
             )?)),
+            subscriptions: Rc::new(Subscriptions::new(id.clone())),
             id,
         })
     }
@@ -357,27 +472,75 @@ impl TrayIcon {
         &self.id
     }
 
+    /// Calls `f` for each event of this tray icon.
+    ///
+    /// The events are still sent to the global [`TrayIconEvent::set_event_handler`] handler
+    /// or [`TrayIconEvent::receiver`] channel. The callback is removed when the last
+    /// instance of this tray icon is dropped.
+    pub fn on_event<F: Fn(TrayIconEvent) + Send + Sync + 'static>(&self, f: F) {
+        self.subscriptions.on_event(Arc::new(f));
+    }
+
+    /// Returns a new channel receiving only the events of this tray icon.
+    ///
+    /// The events are still sent to the global [`TrayIconEvent::set_event_handler`] handler
+    /// or [`TrayIconEvent::receiver`] channel. The channel is disconnected when the last
+    /// instance of this tray icon is dropped.
+    pub fn event_receiver(&self) -> TrayIconEventReceiver {
+        self.subscriptions.event_receiver()
+    }
+
+    /// Returns a new [`Stream`](futures_core::Stream) of the events of this tray icon.
+    ///
+    /// Like [`TrayIcon::event_receiver`], the events are also sent to the global handler
+    /// or channel and the stream ends when the last instance of this tray icon is dropped.
+    #[cfg(feature = "async")]
+    pub fn event_stream(&self) -> TrayIconEventStream {
+        self.subscriptions.event_stream()
+    }
+
     /// Set new tray icon. If `None` is provided, it will remove the icon.
     pub fn set_icon(&self, icon: Option<Icon>) -> Result<()> {
         self.tray.borrow_mut().set_icon(icon)
     }
 
-    /// Set new tray menu.
+    /// Set new tray menu, `None` removes the current menu.
     ///
     /// ## Platform-specific:
     ///
-    /// - **Linux**: once a menu is set it cannot be removed so `None` has no effect
+    /// - **Linux**: Without the `sni` feature, appindicator always needs a menu so `None`
+    ///   replaces the current menu with an empty one. With the `sni` feature, a menu that
+    ///   can't be exported on D-Bus is logged to stderr and the tray icon is left without menu.
     pub fn set_menu(&self, menu: Option<Box<dyn menu::ContextMenu>>) {
         self.tray.borrow_mut().set_menu(menu)
     }
 
+    /// Sets an icon drawn on top of the tray icon, e.g. an unread count or an error marker.
+    ///
+    /// The overlay is kept when the icon is changed with [`TrayIcon::set_icon`].
+    ///
+    /// ## Platform-specific:
+    ///
+    /// - **Linux:** With the `sni` feature, the overlay is sent to the tray host as
+    ///   `OverlayIconPixmap`, which not every host shows. Enable
+    ///   [`TrayIconBuilder::with_composite_overlay`] to have it scaled into the bottom
+    ///   right quarter of the icon instead, as is always done without the `sni` feature.
+    /// - **Windows / macOS:** The overlay is scaled into the bottom right quarter of the icon.
+    ///   On Windows, icons not created with [`Icon::from_rgba`] are shown without the overlay.
+    pub fn set_overlay_icon(&self, icon: Option<Icon>) -> Result<()> {
+        self.tray.borrow_mut().set_overlay_icon(icon)
+    }
+
     /// Sets the tooltip for this tray icon.
     ///
+    /// Accepts a plain string or a [`Tooltip`] with a body and an icon.
+    ///
     /// ## Platform-specific:
     ///
-    /// - **Linux:** Unsupported
-    pub fn set_tooltip<S: AsRef<str>>(&self, tooltip: Option<S>) -> Result<()> {
-        self.tray.borrow_mut().set_tooltip(tooltip)
+    /// - **Linux:** Requires the `sni` feature, unsupported otherwise.
+    /// - **Windows / macOS:** See [`Tooltip`] for how it is shown.
+    pub fn set_tooltip<T: Into<Tooltip>>(&self, tooltip: Option<T>) -> Result<()> {
+        self.tray.borrow_mut().set_tooltip(tooltip.map(Into::into))
     }
 
     /// Sets the tooltip for this tray icon.
@@ -399,10 +562,56 @@ impl TrayIcon {
         self.tray.borrow_mut().set_visible(visible)
     }
 
+    /// Sets the status of this tray icon.
+    ///
+    /// [`TrayStatus::NeedsAttention`] shows the icon set with [`TrayIcon::set_attention_icon`]
+    /// in place of the normal icon, without having to replace it.
+    ///
+    /// ## Platform-specific:
+    ///
+    /// - **Windows / macOS:** [`TrayStatus::Passive`] hides the icon, the other
+    ///   statuses show it, same as [`TrayIcon::set_visible`].
+    pub fn set_status(&self, status: TrayStatus) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        return self.tray.borrow_mut().set_status(status);
+        #[cfg(not(target_os = "linux"))]
+        self.set_visible(status != TrayStatus::Passive)
+    }
+
+    /// Sets the icon shown while the status is [`TrayStatus::NeedsAttention`]. **Linux only**.
+    pub fn set_attention_icon(&self, icon: Option<Icon>) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        return self.tray.borrow_mut().set_attention_icon(icon);
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = icon;
+            Ok(())
+        }
+    }
+
+    /// Sets the name of an animation, looked up in the icon theme, that the tray host
+    /// may play while the status is [`TrayStatus::NeedsAttention`]. **Linux only**.
+    ///
+    /// ## Platform-specific:
+    ///
+    /// - **Linux:** Requires the `sni` feature, unsupported otherwise.
+    pub fn set_attention_movie_name<S: AsRef<str>>(&self, name: Option<S>) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        return self.tray.borrow_mut().set_attention_movie_name(name);
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = name;
+            Ok(())
+        }
+    }
+
     /// Sets the tray icon temp dir path. **Linux only**.
     ///
     /// On Linux, we need to write the icon to the disk and usually it will
-    /// be `$XDG_RUNTIME_DIR/tray-icon` or `$TEMP/tray-icon`.
+    /// be `$XDG_RUNTIME_DIR/tray-icon` or `$TEMP/tray-icon`. Icons are written
+    /// to a subdirectory named after the process id, see [`cleanup_temp_icons`].
+    ///
+    /// Unused with the `sni` feature, where icons are sent over D-Bus.
     pub fn set_temp_dir_path<P: AsRef<Path>>(&self, path: Option<P>) {
         #[cfg(target_os = "linux")]
         self.tray.borrow_mut().set_temp_dir_path(path);
@@ -448,18 +657,83 @@ impl TrayIcon {
     ///
     /// ## Platform-specific:
     ///
-    /// - **Linux**: Unsupported.
+    /// - **Linux**: Requires the `sni` feature. Tray hosts only report where the icon was
+    ///   activated, usually the cursor, so this is a zero-sized rect at the last activation
+    ///   and `None` before the first one. With the `xembed` fallback, this is the geometry
+    ///   of the docked icon. Unsupported without the `sni` feature.
     pub fn rect(&self) -> Option<Rect> {
         self.tray.borrow().rect()
     }
 }
 
+/// Removes the temporary icons left behind by processes that are no longer running. **Linux only**.
+///
+/// Each process holds a lock on its icon directory until it exits, directories
+/// that are not locked anymore are removed, including the ones of processes in
+/// other PID namespaces sharing the temp dir path.
+///
+/// This is done when a tray icon is created, but apps sharing a custom
+/// [`temp_dir_path`](TrayIconAttributes::temp_dir_path) can call it at any time.
+/// Use `None` for the default temp dir path.
+///
+/// ## Platform-specific:
+///
+/// - **Linux:** Does nothing with the `sni` feature, where no file is written.
+pub fn cleanup_temp_icons<P: AsRef<Path>>(temp_dir_path: Option<P>) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    return Ok(platform_impl::cleanup_temp_icons(
+        temp_dir_path.as_ref().map(AsRef::as_ref),
+    )?);
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = temp_dir_path;
+        Ok(())
+    }
+}
+
+/// Returns whether a tray host is running to show the tray icons, apps can use it
+/// to fall back to a regular window instead of running invisibly.
+///
+/// [`TrayIconEvent::HostAvailabilityChanged`] is emitted when this changes.
+///
+/// ## Platform-specific:
+///
+/// - **Linux:** Checks whether a `org.kde.StatusNotifierWatcher` is running or, with
+///   the `xembed` feature, an X11 system tray. Without the `sni` feature, only the
+///   watcher is checked even though `libappindicator` may fall back to the X11 system tray.
+/// - **Windows:** Checks whether the taskbar exists.
+/// - **macOS:** Always `true`.
+pub fn is_tray_available() -> bool {
+    platform_impl::is_tray_available()
+}
+
+/// Returns the maximum time between two clicks to make a double click, as configured by the user.
+///
+/// ## Platform-specific:
+///
+/// - **Linux:** Read from the gtk settings, 400ms until gtk is initialized on the calling thread.
+/// - **Windows:** From `GetDoubleClickTime`.
+/// - **macOS:** From `NSEvent.doubleClickInterval`.
+pub fn double_click_interval() -> Duration {
+    platform_impl::double_click_interval()
+}
+
 /// Describes a tray icon event.
 ///
 /// ## Platform-specific:
 ///
-/// - **Linux**: Unsupported. The event is not emmited even though the icon is shown
-///   and will still show a context menu on right click.
+/// - **Linux**: Only emitted with the `sni` feature, where the `Activate`, `SecondaryActivate`
+///   and `ContextMenu` requests of the tray host are reported as [`TrayIconEvent::Click`] with
+///   [`MouseButton::Left`], [`MouseButton::Middle`] and [`MouseButton::Right`], using the
+///   coordinates given by the host as `position`. Hosts only report activations, so a
+///   `Down` and an `Up` click are emitted back to back and `rect` is a zero-sized rect
+///   at these coordinates.
+///   [`TrayIconEvent::Scroll`] is emitted for the `Scroll` requests of the tray host,
+///   other events are not emitted. Without the `sni` feature, no event is emitted even
+///   though the icon is shown and will still show a context menu on right click.
+///   When the icon is docked with the `xembed` fallback, the input of its window is
+///   reported instead and all events but [`TrayIconEvent::DoubleClick`] are emitted
+///   with the real `position` and `rect`.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(tag = "type"))]
@@ -478,6 +752,10 @@ pub enum TrayIconEvent {
         button: MouseButton,
         /// Mouse button state when this event was triggered.
         button_state: MouseButtonState,
+        /// Keyboard modifiers held when this event was triggered.
+        modifiers: Modifiers,
+        /// When this event happened.
+        timestamp: EventTimestamp,
     },
     /// A double click happened on the tray icon. **Windows Only**
     DoubleClick {
@@ -489,6 +767,10 @@ pub enum TrayIconEvent {
         rect: Rect,
         /// Mouse button that triggered this event.
         button: MouseButton,
+        /// Keyboard modifiers held when this event was triggered.
+        modifiers: Modifiers,
+        /// When this event happened.
+        timestamp: EventTimestamp,
     },
     /// The mouse entered the tray icon region.
     Enter {
@@ -498,6 +780,8 @@ pub enum TrayIconEvent {
         position: dpi::PhysicalPosition<f64>,
         /// Position and size of the tray icon.
         rect: Rect,
+        /// When this event happened.
+        timestamp: EventTimestamp,
     },
     /// The mouse moved over the tray icon region.
     Move {
@@ -507,6 +791,8 @@ pub enum TrayIconEvent {
         position: dpi::PhysicalPosition<f64>,
         /// Position and size of the tray icon.
         rect: Rect,
+        /// When this event happened.
+        timestamp: EventTimestamp,
     },
     /// The mouse left the tray icon region.
     Leave {
@@ -516,7 +802,87 @@ pub enum TrayIconEvent {
         position: dpi::PhysicalPosition<f64>,
         /// Position and size of the tray icon.
         rect: Rect,
+        /// When this event happened.
+        timestamp: EventTimestamp,
     },
+    /// The mouse wheel was scrolled over the tray icon. **Linux Only**
+    ///
+    /// ## Platform-specific:
+    ///
+    /// - **Linux**: Requires the `sni` feature. Tray hosts don't report the cursor
+    ///   so `position` is always zeroed and `rect` is the one of the last activation,
+    ///   see [`TrayIcon::rect`]. With the `xembed` fallback both are known and each
+    ///   wheel step is reported with a `delta` of `1` or `-1`.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    Scroll {
+        /// Id of the tray icon which triggered this event.
+        id: TrayIconId,
+        /// Scroll amount as reported by the tray host, positive values
+        /// usually mean scrolling up or to the right.
+        delta: i32,
+        /// Direction of the scroll.
+        orientation: ScrollOrientation,
+        /// Physical Position of this event.
+        position: dpi::PhysicalPosition<f64>,
+        /// Position and size of the tray icon.
+        rect: Rect,
+        /// When this event happened.
+        timestamp: EventTimestamp,
+    },
+    /// The tray host showing the tray icon appeared or vanished, see [`is_tray_available`].
+    ///
+    /// ## Platform-specific:
+    ///
+    /// - **Linux**: Requires the `sni` feature. Emitted when the `org.kde.StatusNotifierWatcher`
+    ///   appears or vanishes, with the `xembed` feature the icon is only unavailable when
+    ///   there is no X11 system tray either.
+    /// - **Windows**: Only emitted with `available` set to `true`, when the taskbar
+    ///   is created again after Explorer restarted.
+    /// - **macOS**: Unsupported.
+    HostAvailabilityChanged {
+        /// Id of the tray icon which triggered this event.
+        id: TrayIconId,
+        /// Whether a tray host is available to show the tray icon.
+        available: bool,
+        /// When this event happened.
+        timestamp: EventTimestamp,
+    },
+}
+
+/// Describes the direction of a scroll event.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScrollOrientation {
+    Horizontal,
+    Vertical,
+}
+
+/// Describes what kind of application a tray icon belongs to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrayCategory {
+    /// The icon describes the status of a generic application, e.g. a media player.
+    #[default]
+    ApplicationStatus,
+    /// The icon describes the status of a communication oriented application, e.g. a chat client.
+    Communications,
+    /// The icon describes services of the system, e.g. an antivirus or updates.
+    SystemServices,
+    /// The icon describes the state of the hardware, e.g. battery or network.
+    Hardware,
+}
+
+/// Describes the status of a tray icon.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrayStatus {
+    /// The icon is hidden, or shown in a less prominent place by some tray hosts.
+    Passive,
+    /// The icon is shown.
+    #[default]
+    Active,
+    /// The icon is shown and the user is asked to interact with it.
+    NeedsAttention,
 }
 
 /// Describes the mouse button state.
@@ -534,7 +900,7 @@ impl Default for MouseButtonState {
 }
 
 /// Describes which mouse button triggered the event..
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MouseButton {
     Left,
@@ -548,29 +914,40 @@ impl Default for MouseButton {
     }
 }
 
-/// Describes a rectangle including position (x - y axis) and size.
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// Keyboard modifiers held during a click.
+///
+/// ## Platform-specific:
+///
+/// - **Linux**: Only reported with the `xembed` fallback, tray hosts don't
+///   forward modifiers so they are always empty otherwise.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct Rect {
-    pub size: dpi::PhysicalSize<u32>,
-    pub position: dpi::PhysicalPosition<f64>,
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Modifiers {
+    /// The Shift key.
+    pub shift: bool,
+    /// The Control key.
+    pub control: bool,
+    /// The Alt key, Option on macOS.
+    pub alt: bool,
+    /// The Super key, Windows on Windows and Command on macOS.
+    pub super_key: bool,
 }
 
-impl Default for Rect {
-    fn default() -> Self {
-        Self {
-            size: dpi::PhysicalSize::new(0, 0),
-            position: dpi::PhysicalPosition::new(0., 0.),
-        }
+impl Modifiers {
+    /// Returns `true` if no modifier is held.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
     }
 }
 
 /// A reciever that could be used to listen to tray events.
 pub type TrayIconEventReceiver = Receiver<TrayIconEvent>;
-type TrayIconEventHandler = Box<dyn Fn(TrayIconEvent) + Send + Sync + 'static>;
+/// A handler called for new tray events, see [`TrayIconEvent::set_event_handler`].
+pub type TrayIconEventHandler = Arc<dyn Fn(TrayIconEvent) + Send + Sync + 'static>;
 
 static TRAY_CHANNEL: Lazy<(Sender<TrayIconEvent>, TrayIconEventReceiver)> = Lazy::new(unbounded);
-static TRAY_EVENT_HANDLER: OnceCell<Option<TrayIconEventHandler>> = OnceCell::new();
+static TRAY_EVENT_HANDLER: RwLock<Option<TrayIconEventHandler>> = RwLock::new(None);
 
 impl TrayIconEvent {
     /// Returns the id of the tray icon which triggered this event.
@@ -581,6 +958,33 @@ impl TrayIconEvent {
             TrayIconEvent::Enter { id, .. } => id,
             TrayIconEvent::Move { id, .. } => id,
             TrayIconEvent::Leave { id, .. } => id,
+            TrayIconEvent::Scroll { id, .. } => id,
+            TrayIconEvent::HostAvailabilityChanged { id, .. } => id,
+        }
+    }
+
+    /// Returns when this event happened.
+    pub fn timestamp(&self) -> &EventTimestamp {
+        match self {
+            TrayIconEvent::Click { timestamp, .. } => timestamp,
+            TrayIconEvent::DoubleClick { timestamp, .. } => timestamp,
+            TrayIconEvent::Enter { timestamp, .. } => timestamp,
+            TrayIconEvent::Move { timestamp, .. } => timestamp,
+            TrayIconEvent::Leave { timestamp, .. } => timestamp,
+            TrayIconEvent::Scroll { timestamp, .. } => timestamp,
+            TrayIconEvent::HostAvailabilityChanged { timestamp, .. } => timestamp,
+        }
+    }
+
+    fn timestamp_mut(&mut self) -> &mut EventTimestamp {
+        match self {
+            TrayIconEvent::Click { timestamp, .. } => timestamp,
+            TrayIconEvent::DoubleClick { timestamp, .. } => timestamp,
+            TrayIconEvent::Enter { timestamp, .. } => timestamp,
+            TrayIconEvent::Move { timestamp, .. } => timestamp,
+            TrayIconEvent::Leave { timestamp, .. } => timestamp,
+            TrayIconEvent::Scroll { timestamp, .. } => timestamp,
+            TrayIconEvent::HostAvailabilityChanged { timestamp, .. } => timestamp,
         }
     }
 
@@ -594,23 +998,66 @@ impl TrayIconEvent {
         &TRAY_CHANNEL.1
     }
 
+    /// Returns a new [`Stream`](futures_core::Stream) of the events of all tray icons.
+    ///
+    /// Each stream receives every event sent after it was created, regardless of
+    /// [`TrayIconEvent::set_event_handler`] and of the other streams or receivers.
+    /// Events are queued until the stream is polled, so a stream that is no longer
+    /// polled should be dropped.
+    #[cfg(feature = "async")]
+    pub fn stream() -> TrayIconEventStream {
+        TrayIconEventStream::global()
+    }
+
+    /// Returns a new subscriber receiving every event of all tray icons in its own
+    /// unbounded queue.
+    ///
+    /// Unlike [`TrayIconEvent::receiver`], which hands each event to a single receiver,
+    /// every subscriber gets all the events sent after it was created, regardless of
+    /// [`TrayIconEvent::set_event_handler`].
+    pub fn subscribe() -> TrayIconEventSubscriber {
+        Self::subscribe_with(SubscribeOptions::default())
+    }
+
+    /// Like [`TrayIconEvent::subscribe`] with a bounded queue or coalesced
+    /// [`TrayIconEvent::Move`] events, see [`SubscribeOptions`].
+    pub fn subscribe_with(options: SubscribeOptions) -> TrayIconEventSubscriber {
+        TrayIconEventSubscriber::new(options)
+    }
+
     /// Set a handler to be called for new events. Useful for implementing custom event sender.
     ///
+    /// The handler can be replaced at any time, and calling this function with `None`
+    /// sends new events to the channel associated with [`TrayIconEvent::receiver`] again.
+    /// Returns the previous handler, if any.
+    ///
     /// ## Note
     ///
     /// Calling this function with a `Some` value,
     /// will not send new events to the channel associated with [`TrayIconEvent::receiver`]
-    pub fn set_event_handler<F: Fn(TrayIconEvent) + Send + Sync + 'static>(f: Option<F>) {
-        if let Some(f) = f {
-            let _ = TRAY_EVENT_HANDLER.set(Some(Box::new(f)));
-        } else {
-            let _ = TRAY_EVENT_HANDLER.set(None);
-        }
+    ///
+    /// Events being delivered while the handler is replaced still go to the previous
+    /// handler, the new one only receives the events sent after this function returns.
+    /// The handler is called without holding any lock, so it may replace itself.
+    pub fn set_event_handler<F: Fn(TrayIconEvent) + Send + Sync + 'static>(
+        f: Option<F>,
+    ) -> Option<TrayIconEventHandler> {
+        let handler = f.map(|f| Arc::new(f) as TrayIconEventHandler);
+        let mut current = TRAY_EVENT_HANDLER
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, handler)
     }
 
     #[allow(unused)]
-    pub(crate) fn send(event: TrayIconEvent) {
-        if let Some(handler) = TRAY_EVENT_HANDLER.get_or_init(|| None) {
+    pub(crate) fn send(mut event: TrayIconEvent) {
+        event.timestamp_mut().assign_sequence();
+        subscription::dispatch(&event);
+        let handler = TRAY_EVENT_HANDLER
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(handler) = handler {
             handler(event);
         } else {
             let _ = TRAY_CHANNEL.0.send(event);
@@ -620,6 +1067,53 @@ impl TrayIconEvent {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Mutex;
+
+    /// Held by tests receiving tray events so they don't steal each other's events.
+    pub(crate) static EVENTS: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn replaces_event_handler() {
+        use super::*;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let _events = EVENTS.lock().unwrap();
+        let id = TrayIconId::new("handler");
+        let event = || TrayIconEvent::HostAvailabilityChanged {
+            id: id.clone(),
+            available: true,
+            timestamp: EventTimestamp::now(),
+        };
+
+        let first = Arc::new(AtomicUsize::new(0));
+        let second = Arc::new(AtomicUsize::new(0));
+
+        let counter = first.clone();
+        let previous = TrayIconEvent::set_event_handler(Some(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        assert!(previous.is_none());
+        TrayIconEvent::send(event());
+
+        let counter = second.clone();
+        let previous = TrayIconEvent::set_event_handler(Some(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        TrayIconEvent::send(event());
+        assert_eq!(first.load(Ordering::SeqCst), 1);
+        assert_eq!(second.load(Ordering::SeqCst), 1);
+
+        // the returned handler is still usable
+        previous.unwrap()(event());
+        assert_eq!(first.load(Ordering::SeqCst), 2);
+
+        assert!(TrayIconEvent::set_event_handler(None::<fn(TrayIconEvent)>).is_some());
+        TrayIconEvent::send(event());
+        assert_eq!(second.load(Ordering::SeqCst), 1);
+        assert!(TrayIconEvent::receiver()
+            .try_iter()
+            .any(|event| event.id() == &id));
+    }
 
     #[cfg(feature = "serde")]
     #[test]
@@ -631,6 +1125,16 @@ mod tests {
             id: TrayIconId::new("id"),
             position: dpi::PhysicalPosition::default(),
             rect: Rect::default(),
+            modifiers: Modifiers {
+                shift: true,
+                super_key: true,
+                ..Default::default()
+            },
+            timestamp: EventTimestamp {
+                instant: *timestamp::EPOCH,
+                platform_time: Some(42),
+                sequence: 7,
+            },
         };
 
         let value = serde_json::to_value(&event).unwrap();
@@ -641,6 +1145,17 @@ mod tests {
                 "button": "Left",
                 "buttonState": "Down",
                 "id": "id",
+                "modifiers": {
+                    "shift": true,
+                    "control": false,
+                    "alt": false,
+                    "superKey": true,
+                },
+                "timestamp": {
+                    "instant": 0,
+                    "platformTime": 42,
+                    "sequence": 7,
+                },
                 "position": {
                     "x": 0.0,
                     "y": 0.0,
@@ -658,4 +1173,62 @@ mod tests {
             })
         )
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_serializes_scroll() {
+        use super::*;
+        let event = TrayIconEvent::Scroll {
+            id: TrayIconId::new("id"),
+            delta: -120,
+            orientation: ScrollOrientation::Vertical,
+            position: dpi::PhysicalPosition::default(),
+            rect: Rect::default(),
+            timestamp: EventTimestamp {
+                instant: *timestamp::EPOCH,
+                platform_time: None,
+                sequence: 1,
+            },
+        };
+
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "type": "Scroll",
+                "id": "id",
+                "delta": -120,
+                "orientation": "Vertical",
+                "timestamp": {
+                    "instant": 0,
+                    "platformTime": null,
+                    "sequence": 1,
+                },
+                "position": {
+                    "x": 0.0,
+                    "y": 0.0,
+                },
+                "rect": {
+                    "size": {
+                        "width": 0,
+                        "height": 0,
+                    },
+                    "position": {
+                        "x": 0.0,
+                        "y": 0.0,
+                    },
+                }
+            })
+        );
+
+        let event: TrayIconEvent = serde_json::from_value(value).unwrap();
+        assert!(matches!(
+            event,
+            TrayIconEvent::Scroll {
+                delta: -120,
+                orientation: ScrollOrientation::Vertical,
+                ..
+            }
+        ));
+    }
 }
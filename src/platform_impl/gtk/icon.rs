@@ -4,7 +4,7 @@
 
 use std::{fs::File, io::BufWriter, path::Path};
 
-use crate::icon::BadIcon;
+use crate::icon::{BadIcon, RgbaIcon};
 
 #[derive(Debug, Clone)]
 pub struct PlatformIcon {
@@ -22,6 +22,14 @@ impl PlatformIcon {
         })
     }
 
+    pub fn to_rgba(&self) -> Option<RgbaIcon> {
+        Some(RgbaIcon {
+            rgba: self.rgba.clone(),
+            width: self.width as u32,
+            height: self.height as u32,
+        })
+    }
+
     pub fn write_to_png(&self, path: impl AsRef<Path>) -> crate::Result<()> {
         let png = File::create(path)?;
         let w = &mut BufWriter::new(png);
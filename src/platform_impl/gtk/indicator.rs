@@ -0,0 +1,68 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Minimal binding of `AppIndicator`, the one from `libappindicator` always
+//! uses the `ApplicationStatus` category which can only be set on creation.
+
+use gtk::glib::{translate::ToGlibPtr, ObjectType};
+use libappindicator::{
+    _AppIndicator, app_indicator_new, app_indicator_set_attention_icon_full,
+    app_indicator_set_icon_full, app_indicator_set_icon_theme_path, app_indicator_set_label,
+    app_indicator_set_menu, app_indicator_set_status, AppIndicatorCategory, AppIndicatorStatus,
+};
+
+use crate::TrayCategory;
+
+pub struct AppIndicator {
+    air: *mut _AppIndicator,
+}
+
+impl AppIndicator {
+    pub fn new(id: &str, category: TrayCategory) -> Self {
+        let category = match category {
+            TrayCategory::ApplicationStatus => AppIndicatorCategory::ApplicationStatus,
+            TrayCategory::Communications => AppIndicatorCategory::Communications,
+            TrayCategory::SystemServices => AppIndicatorCategory::SystemServices,
+            TrayCategory::Hardware => AppIndicatorCategory::Hardware,
+        };
+
+        Self {
+            air: unsafe {
+                app_indicator_new(id.to_glib_none().0, "".to_glib_none().0, category as u32)
+            },
+        }
+    }
+
+    pub fn set_status(&mut self, status: AppIndicatorStatus) {
+        unsafe { app_indicator_set_status(self.air, status as u32) }
+    }
+
+    pub fn set_menu(&mut self, menu: &gtk::Menu) {
+        unsafe { app_indicator_set_menu(self.air, menu.as_ptr()) }
+    }
+
+    pub fn set_label(&mut self, label: &str, guide: &str) {
+        unsafe { app_indicator_set_label(self.air, label.to_glib_none().0, guide.to_glib_none().0) }
+    }
+
+    pub fn set_icon_theme_path(&mut self, path: &str) {
+        unsafe { app_indicator_set_icon_theme_path(self.air, path.to_glib_none().0) }
+    }
+
+    pub fn set_icon_full(&mut self, name: &str, desc: &str) {
+        unsafe {
+            app_indicator_set_icon_full(self.air, name.to_glib_none().0, desc.to_glib_none().0)
+        }
+    }
+
+    pub fn set_attention_icon_full(&mut self, name: &str, desc: &str) {
+        unsafe {
+            app_indicator_set_attention_icon_full(
+                self.air,
+                name.to_glib_none().0,
+                desc.to_glib_none().0,
+            )
+        }
+    }
+}
@@ -3,32 +3,45 @@
 // SPDX-License-Identifier: MIT
 
 mod icon;
+mod indicator;
+mod temp_icons;
 use std::path::{Path, PathBuf};
 
+use gtk::{gio, glib::ToVariant};
+
 use crate::icon::Icon;
 pub(crate) use icon::PlatformIcon;
+pub(crate) use temp_icons::cleanup_temp_icons;
+use temp_icons::{release_temp_dir, temp_icon_path};
 
-use crate::{TrayIconAttributes, TrayIconId, COUNTER};
-use libappindicator::{AppIndicator, AppIndicatorStatus};
+use crate::{TrayIconAttributes, TrayIconId, TrayStatus, COUNTER};
+use indicator::AppIndicator;
+use libappindicator::AppIndicatorStatus;
 
 pub struct TrayIcon {
     id: u32,
     indicator: AppIndicator,
     temp_dir_path: Option<PathBuf>,
     path: PathBuf,
+    attention_path: Option<PathBuf>,
     counter: u32,
+    icon: Option<Icon>,
+    overlay_icon: Option<Icon>,
     menu: Option<Box<dyn muda::ContextMenu>>,
 }
 
 impl TrayIcon {
-    pub fn new(_id: TrayIconId, attrs: TrayIconAttributes) -> crate::Result<Self> {
+    pub fn new(tray_id: TrayIconId, attrs: TrayIconAttributes) -> crate::Result<Self> {
         let id = COUNTER.next();
-        let mut indicator = AppIndicator::new("tray-icon tray app", "");
+        // icons of crashed processes are never removed by `Drop`
+        let _ = cleanup_temp_icons(attrs.temp_dir_path.as_deref());
+
+        let mut indicator = AppIndicator::new(&attrs.app_id_or_default(&tray_id), attrs.category);
         indicator.set_status(AppIndicatorStatus::Active);
 
         let (parent_path, icon_path) = temp_icon_path(attrs.temp_dir_path.as_ref(), id, 0)?;
 
-        if let Some(icon) = attrs.icon {
+        if let Some(icon) = &attrs.icon {
             icon.inner.write_to_png(&icon_path)?;
         }
 
@@ -36,7 +49,7 @@ impl TrayIcon {
         indicator.set_icon_full(&icon_path.to_string_lossy(), "icon");
 
         if let Some(menu) = &attrs.menu {
-            indicator.set_menu(&mut menu.gtk_context_menu());
+            indicator.set_menu(&menu.gtk_context_menu());
         }
 
         if let Some(title) = attrs.title {
@@ -47,12 +60,26 @@ impl TrayIcon {
             id,
             indicator,
             path: icon_path,
+            attention_path: None,
             temp_dir_path: attrs.temp_dir_path,
             counter: 0,
+            icon: attrs.icon,
+            overlay_icon: None,
             menu: attrs.menu,
         })
     }
     pub fn set_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.icon = icon;
+        self.update_icon()
+    }
+
+    pub fn set_overlay_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.overlay_icon = icon;
+        self.update_icon()
+    }
+
+    /// Writes the icon, with the overlay drawn on top since appindicator has no overlay support.
+    fn update_icon(&mut self) -> crate::Result<()> {
         let _ = std::fs::remove_file(&self.path);
 
         self.counter += 1;
@@ -60,7 +87,7 @@ impl TrayIcon {
         let (parent_path, icon_path) =
             temp_icon_path(self.temp_dir_path.as_ref(), self.id, self.counter)?;
 
-        if let Some(icon) = icon {
+        if let Some(icon) = Icon::with_overlay(self.icon.as_ref(), self.overlay_icon.as_ref()) {
             icon.inner.write_to_png(&icon_path)?;
         }
 
@@ -74,13 +101,16 @@ impl TrayIcon {
     }
 
     pub fn set_menu(&mut self, menu: Option<Box<dyn crate::menu::ContextMenu>>) {
-        if let Some(menu) = &menu {
-            self.indicator.set_menu(&mut menu.gtk_context_menu());
+        match &menu {
+            Some(menu) => self.indicator.set_menu(&menu.gtk_context_menu()),
+            // appindicator can't remove a menu and keeps showing the old one
+            // even once its items are gone, replace it with an empty one instead
+            None => self.indicator.set_menu(&gtk::Menu::new()),
         }
         self.menu = menu;
     }
 
-    pub fn set_tooltip<S: AsRef<str>>(&mut self, _tooltip: Option<S>) -> crate::Result<()> {
+    pub fn set_tooltip(&mut self, _tooltip: Option<crate::Tooltip>) -> crate::Result<()> {
         Ok(())
     }
 
@@ -99,6 +129,45 @@ impl TrayIcon {
         Ok(())
     }
 
+    pub fn set_status(&mut self, status: TrayStatus) -> crate::Result<()> {
+        self.indicator.set_status(match status {
+            TrayStatus::Passive => AppIndicatorStatus::Passive,
+            TrayStatus::Active => AppIndicatorStatus::Active,
+            TrayStatus::NeedsAttention => AppIndicatorStatus::Attention,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_attention_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        if let Some(path) = self.attention_path.take() {
+            let _ = std::fs::remove_file(path);
+        }
+
+        let Some(icon) = icon else {
+            self.indicator.set_attention_icon_full("", "");
+            return Ok(());
+        };
+
+        self.counter += 1;
+
+        let (_, icon_path) = temp_icon_path(self.temp_dir_path.as_ref(), self.id, self.counter)?;
+        icon.inner.write_to_png(&icon_path)?;
+
+        self.indicator
+            .set_attention_icon_full(&icon_path.to_string_lossy(), "tray attention icon");
+        self.attention_path = Some(icon_path);
+
+        Ok(())
+    }
+
+    pub fn set_attention_movie_name<S: AsRef<str>>(
+        &mut self,
+        _name: Option<S>,
+    ) -> crate::Result<()> {
+        Ok(())
+    }
+
     pub fn set_temp_dir_path<P: AsRef<Path>>(&mut self, path: Option<P>) {
         self.temp_dir_path = path.map(|p| p.as_ref().to_path_buf());
     }
@@ -112,45 +181,36 @@ impl Drop for TrayIcon {
     fn drop(&mut self) {
         self.indicator.set_status(AppIndicatorStatus::Passive);
         let _ = std::fs::remove_file(&self.path);
+        if let Some(path) = &self.attention_path {
+            let _ = std::fs::remove_file(path);
+        }
+        // only done once the last tray icon of this process is gone
+        if let Some(parent) = self.path.parent() {
+            release_temp_dir(parent);
+        }
     }
 }
 
-/// Generates an icon path in one of the following dirs:
-/// 1. If `temp_icon_dir` is `Some` use that.
-/// 2. `$XDG_RUNTIME_DIR/tray-icon`
-/// 3. `/tmp/tray-icon`
-fn temp_icon_path(
-    temp_icon_dir: Option<&PathBuf>,
-    id: u32,
-    counter: u32,
-) -> std::io::Result<(PathBuf, PathBuf)> {
-    let parent_path = match temp_icon_dir.as_ref() {
-        Some(path) => path.to_path_buf(),
-        None => dirs::runtime_dir()
-            .unwrap_or_else(std::env::temp_dir)
-            .join("tray-icon"),
+/// Only the watcher is checked, `libappindicator` falls back to the X11 system
+/// tray on its own when there is none.
+pub fn is_tray_available() -> bool {
+    let Ok(connection) = gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE) else {
+        return false;
     };
 
-    std::fs::create_dir_all(&parent_path)?;
-    let icon_path = parent_path.join(format!("tray-icon-{}-{}.png", id, counter));
-    Ok((parent_path, icon_path))
-}
-
-#[test]
-fn temp_icon_path_preference_order() {
-    let runtime_dir = option_env!("XDG_RUNTIME_DIR");
-    let override_dir = PathBuf::from("/tmp/tao-tests");
-
-    let (dir1, _file1) = temp_icon_path(Some(&override_dir), 00, 00).unwrap();
-    let (dir2, _file1) = temp_icon_path(None, 00, 00).unwrap();
-    std::env::remove_var("XDG_RUNTIME_DIR");
-    let (dir3, _file2) = temp_icon_path(None, 00, 00).unwrap();
-
-    assert_eq!(dir1, override_dir);
-    if let Some(runtime_dir) = runtime_dir {
-        std::env::set_var("XDG_RUNTIME_DIR", runtime_dir);
-        assert_eq!(dir2, PathBuf::from(format!("{}/tray-icon", runtime_dir)));
-    }
-
-    assert_eq!(dir3, PathBuf::from("/tmp/tray-icon"));
+    connection
+        .call_sync(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameHasOwner",
+            Some(&("org.kde.StatusNotifierWatcher",).to_variant()),
+            None,
+            gio::DBusCallFlags::NONE,
+            -1,
+            gio::Cancellable::NONE,
+        )
+        .ok()
+        .and_then(|reply| reply.get::<(bool,)>())
+        .is_some_and(|(has_owner,)| has_owner)
 }
@@ -0,0 +1,302 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Icons are written to a subdirectory named after the process id so the ones
+//! left behind by crashed processes can be found and removed.
+//!
+//! Each process holds an `flock` on a lock file in its subdirectory until it exits.
+//! The kernel releases it even if the process crashes, so a subdirectory whose lock
+//! can be taken is no longer used. Unlike checking `/proc/<pid>`, this also works for
+//! processes in another PID namespace, e.g. containers and Flatpak sandboxes.
+//!
+//! Subdirectories are renamed while their lock is held before being removed, and a
+//! process checks its lock file is still in place once it has locked it, so it never
+//! ends up using a subdirectory that is being removed.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    os::{fd::AsRawFd, unix::fs::MetadataExt},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+
+/// Name of the lock file in the subdirectory of each process.
+const LOCK_FILE: &str = ".lock";
+
+/// Prefix of the name subdirectories are renamed to before being removed.
+const REMOVED_PREFIX: &str = ".removed-";
+
+/// How many times locking a subdirectory held by another process is attempted.
+const LOCK_ATTEMPTS: u32 = 5;
+
+/// Lock files held by this process, by subdirectory.
+static LOCKS: Lazy<Mutex<HashMap<PathBuf, File>>> = Lazy::new(Default::default);
+
+/// Returns the directory shared by all processes, one of the following dirs:
+/// 1. If `temp_icon_dir` is `Some` use that.
+/// 2. `$XDG_RUNTIME_DIR/tray-icon`
+/// 3. `/tmp/tray-icon`
+fn base_dir(temp_icon_dir: Option<&Path>) -> PathBuf {
+    match temp_icon_dir {
+        Some(path) => path.to_path_buf(),
+        None => dirs::runtime_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("tray-icon"),
+    }
+}
+
+/// Generates an icon path in the directory of the current process,
+/// see [`base_dir`] for where it is created.
+pub fn temp_icon_path(
+    temp_icon_dir: Option<&PathBuf>,
+    id: u32,
+    counter: u32,
+) -> io::Result<(PathBuf, PathBuf)> {
+    let parent_path =
+        base_dir(temp_icon_dir.map(|p| p.as_path())).join(std::process::id().to_string());
+
+    lock_dir(&parent_path)?;
+    let icon_path = parent_path.join(format!("tray-icon-{}-{}.png", id, counter));
+    Ok((parent_path, icon_path))
+}
+
+/// Creates `dir` and locks it until this process exits, if not done already.
+fn lock_dir(dir: &Path) -> io::Result<()> {
+    let mut locks = LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    if locks.contains_key(dir) {
+        return Ok(());
+    }
+
+    let path = dir.join(LOCK_FILE);
+    for _ in 0..LOCK_ATTEMPTS {
+        std::fs::create_dir_all(dir)?;
+        let file = match File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+        {
+            Ok(file) => file,
+            // removed by a cleanup in the meantime
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        // Held by a cleanup about to remove the directory, or by a process with
+        // the same pid in another PID namespace.
+        if !try_lock(&file)? {
+            std::thread::sleep(Duration::from_millis(20));
+            continue;
+        }
+
+        // a cleanup may have removed the directory before the lock was taken
+        if is_same_file(&file, &path) {
+            locks.insert(dir.to_path_buf(), file);
+            return Ok(());
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::WouldBlock,
+        format!("{} is locked by another process", path.display()),
+    ))
+}
+
+/// Whether `path` still refers to the opened `file`.
+fn is_same_file(file: &File, path: &Path) -> bool {
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(file), Ok(path)) => file.dev() == path.dev() && file.ino() == path.ino(),
+        _ => false,
+    }
+}
+
+/// Removes `dir` once the last icon in it is gone, releasing its lock.
+pub fn release_temp_dir(dir: &Path) {
+    let only_lock = std::fs::read_dir(dir)
+        .map(|mut entries| entries.all(|entry| entry.is_ok_and(|e| e.file_name() == LOCK_FILE)))
+        .unwrap_or(false);
+    if !only_lock {
+        return;
+    }
+
+    LOCKS.lock().unwrap_or_else(|e| e.into_inner()).remove(dir);
+    let _ = std::fs::remove_file(dir.join(LOCK_FILE));
+    let _ = std::fs::remove_dir(dir);
+}
+
+/// Takes an exclusive lock on `file` without waiting, returns `false` if it is held elsewhere.
+///
+/// Locks taken through different opens of the same file conflict, even within a process.
+fn try_lock(file: &File) -> io::Result<bool> {
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+
+    let error = io::Error::last_os_error();
+    if error.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(error)
+    }
+}
+
+/// Removes the directories of processes that are no longer running, the ones
+/// whose lock file isn't locked anymore.
+///
+/// Directories without lock file may be being created and are kept, like the
+/// ones that can't be removed, e.g. owned by another user. Icons written directly
+/// into the base directory by previous versions are removed as well.
+pub fn cleanup_temp_icons(temp_icon_dir: Option<&Path>) -> io::Result<()> {
+    let base = base_dir(temp_icon_dir);
+    let entries = match std::fs::read_dir(&base) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    for entry in entries.flatten() {
+        let Some(name) = entry.file_name().to_str().map(ToString::to_string) else {
+            continue;
+        };
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_file() && name.starts_with("tray-icon-") && name.ends_with(".png") {
+            let _ = std::fs::remove_file(entry.path());
+            continue;
+        }
+        if !file_type.is_dir() {
+            continue;
+        }
+
+        // left behind by a cleanup that didn't finish
+        if name.starts_with(REMOVED_PREFIX) {
+            let _ = std::fs::remove_dir_all(entry.path());
+            continue;
+        }
+
+        let Ok(pid) = name.parse::<u32>() else {
+            continue;
+        };
+        if pid == std::process::id() {
+            continue;
+        }
+
+        let Ok(lock) = File::open(entry.path().join(LOCK_FILE)) else {
+            continue;
+        };
+        // Moved away while locked, a process creating the directory again meanwhile
+        // either can't lock the old lock file or finds it gone once it has the lock.
+        if try_lock(&lock).unwrap_or(false) {
+            let removed = base.join(format!("{REMOVED_PREFIX}{pid}-{}", std::process::id()));
+            if std::fs::rename(entry.path(), &removed).is_ok() {
+                let _ = std::fs::remove_dir_all(removed);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_icon_path_preference_order() {
+        let runtime_dir = option_env!("XDG_RUNTIME_DIR");
+        let override_dir = PathBuf::from("/tmp/tao-tests");
+        let pid = std::process::id().to_string();
+
+        let (dir1, _file1) = temp_icon_path(Some(&override_dir), 00, 00).unwrap();
+        let (dir2, _file1) = temp_icon_path(None, 00, 00).unwrap();
+        std::env::remove_var("XDG_RUNTIME_DIR");
+        let (dir3, _file2) = temp_icon_path(None, 00, 00).unwrap();
+
+        assert_eq!(dir1, override_dir.join(&pid));
+        if let Some(runtime_dir) = runtime_dir {
+            std::env::set_var("XDG_RUNTIME_DIR", runtime_dir);
+            assert_eq!(
+                dir2,
+                PathBuf::from(format!("{}/tray-icon/{}", runtime_dir, pid))
+            );
+        }
+
+        assert_eq!(dir3, PathBuf::from(format!("/tmp/tray-icon/{}", pid)));
+    }
+
+    #[test]
+    fn cleanup_removes_unlocked_directories_only() {
+        let base = std::env::temp_dir().join(format!("tray-icon-cleanup-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+
+        let (own, own_icon) = temp_icon_path(Some(&base), 0, 0).unwrap();
+        std::fs::write(&own_icon, []).unwrap();
+        assert!(own.join(LOCK_FILE).exists());
+
+        // pids that are never used, the lock decides whether they are alive
+        let dead = base.join(u32::MAX.to_string());
+        let released = base.join((u32::MAX - 1).to_string());
+        let alive = base.join((u32::MAX - 2).to_string());
+        let no_lock_file = base.join((u32::MAX - 3).to_string());
+        let other = base.join("not-a-pid");
+        for dir in [&dead, &released, &alive, &no_lock_file, &other] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("tray-icon-0-0.png"), []).unwrap();
+        }
+        for dir in [&dead, &released, &alive] {
+            std::fs::write(dir.join(LOCK_FILE), []).unwrap();
+        }
+        // another open of the lock file behaves like another process holding it
+        let lock = File::open(released.join(LOCK_FILE)).unwrap();
+        assert!(try_lock(&lock).unwrap());
+        drop(lock);
+        let lock = File::open(alive.join(LOCK_FILE)).unwrap();
+        assert!(try_lock(&lock).unwrap());
+        assert_eq!(
+            lock_dir(&alive).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+
+        // written directly into the base directory by previous versions
+        let flat_icon = base.join("tray-icon-0-0.png");
+        let unrelated = base.join("icon.png");
+        for file in [&flat_icon, &unrelated] {
+            std::fs::write(file, []).unwrap();
+        }
+
+        cleanup_temp_icons(Some(&base)).unwrap();
+
+        assert!(own.exists());
+        assert!(!dead.exists());
+        assert!(!released.exists());
+        assert!(alive.exists());
+        assert!(no_lock_file.exists());
+        assert!(other.exists());
+        assert!(!flat_icon.exists());
+        assert!(unrelated.exists());
+        assert!(std::fs::read_dir(&base)
+            .unwrap()
+            .flatten()
+            .all(|entry| !entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(REMOVED_PREFIX)));
+
+        // released once the last icon is gone
+        release_temp_dir(&own);
+        assert!(own.exists());
+        std::fs::remove_file(own_icon).unwrap();
+        release_temp_dir(&own);
+        assert!(!own.exists());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,22 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Settings read from gtk, shared by the Linux backends.
+
+use std::time::Duration;
+
+use gtk::prelude::GtkSettingsExt;
+
+/// Default of the `gtk-double-click-time` setting, in milliseconds.
+const DEFAULT_DOUBLE_CLICK_TIME: i32 = 400;
+
+/// Read from the gtk settings once gtk is initialized on this thread,
+/// the gtk default otherwise.
+pub fn double_click_interval() -> Duration {
+    let millis = gtk::is_initialized_main_thread()
+        .then(gtk::Settings::default)
+        .flatten()
+        .map(|settings| settings.gtk_double_click_time());
+    Duration::from_millis(millis.unwrap_or(DEFAULT_DOUBLE_CLICK_TIME).max(0) as u64)
+}
@@ -13,6 +13,10 @@ impl PlatformIcon {
         Ok(PlatformIcon(RgbaIcon::from_rgba(rgba, width, height)?))
     }
 
+    pub fn to_rgba(&self) -> Option<RgbaIcon> {
+        Some(self.0.clone())
+    }
+
     pub fn get_size(&self) -> (u32, u32) {
         (self.0.width, self.0.height)
     }
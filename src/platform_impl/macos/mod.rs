@@ -3,13 +3,16 @@
 // SPDX-License-Identifier: MIT
 
 mod icon;
-use std::cell::{Cell, RefCell};
+use std::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
 
 use objc2::rc::Retained;
 use objc2::{define_class, msg_send, AllocAnyThread, DeclaredClass, Message};
 use objc2_app_kit::{
-    NSCellImagePosition, NSEvent, NSImage, NSMenu, NSStatusBar, NSStatusItem, NSTrackingArea,
-    NSTrackingAreaOptions, NSVariableStatusItemLength, NSView, NSWindow,
+    NSCellImagePosition, NSEvent, NSEventModifierFlags, NSImage, NSMenu, NSStatusBar, NSStatusItem,
+    NSTrackingArea, NSTrackingAreaOptions, NSVariableStatusItemLength, NSView, NSWindow,
 };
 use objc2_core_foundation::{CGPoint, CGRect, CGSize};
 use objc2_core_graphics::{CGDisplayPixelsHigh, CGMainDisplayID};
@@ -18,8 +21,8 @@ use objc2_foundation::{MainThreadMarker, NSData, NSSize, NSString};
 pub(crate) use self::icon::PlatformIcon;
 use crate::Error;
 use crate::{
-    icon::Icon, menu, MouseButton, MouseButtonState, Rect, TrayIconAttributes, TrayIconEvent,
-    TrayIconId,
+    icon::Icon, menu, EventTimestamp, Modifiers, MouseButton, MouseButtonState, Rect, Tooltip,
+    TrayIconAttributes, TrayIconEvent, TrayIconId,
 };
 
 pub struct TrayIcon {
@@ -27,19 +30,21 @@ pub struct TrayIcon {
     tray_target: Option<Retained<TrayTarget>>,
     id: TrayIconId,
     attrs: TrayIconAttributes,
+    overlay_icon: Option<Icon>,
     mtm: MainThreadMarker,
 }
 
 impl TrayIcon {
     pub fn new(id: TrayIconId, attrs: TrayIconAttributes) -> crate::Result<Self> {
         let mtm = MainThreadMarker::new().ok_or(Error::NotMainThread)?;
-        let (ns_status_item, tray_target) = Self::create(&id, &attrs, mtm)?;
+        let (ns_status_item, tray_target) = Self::create(&id, &attrs, None, mtm)?;
 
         let tray_icon = Self {
             ns_status_item: Some(ns_status_item),
             tray_target: Some(tray_target),
             id,
             attrs,
+            overlay_icon: None,
             mtm,
         };
 
@@ -49,6 +54,7 @@ impl TrayIcon {
     fn create(
         id: &TrayIconId,
         attrs: &TrayIconAttributes,
+        overlay_icon: Option<&Icon>,
         mtm: MainThreadMarker,
     ) -> crate::Result<(Retained<NSStatusItem>, Retained<TrayTarget>)> {
         let ns_status_item = unsafe {
@@ -57,7 +63,7 @@ impl TrayIcon {
 
         set_icon_for_ns_status_item_button(
             &ns_status_item,
-            attrs.icon.clone(),
+            Icon::with_overlay(attrs.icon.as_ref(), overlay_icon),
             attrs.icon_is_template,
             mtm,
         )?;
@@ -68,7 +74,11 @@ impl TrayIcon {
             }
         }
 
-        Self::set_tooltip_inner(&ns_status_item, attrs.tooltip.clone(), mtm)?;
+        Self::set_tooltip_inner(
+            &ns_status_item,
+            attrs.tooltip.as_ref().map(Tooltip::to_text),
+            mtm,
+        )?;
         Self::set_title_inner(&ns_status_item, attrs.title.clone(), mtm);
 
         let tray_target = unsafe {
@@ -114,13 +124,24 @@ impl TrayIcon {
     pub fn set_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
         if let (Some(ns_status_item), Some(tray_target)) = (&self.ns_status_item, &self.tray_target)
         {
-            set_icon_for_ns_status_item_button(ns_status_item, icon.clone(), false, self.mtm)?;
+            set_icon_for_ns_status_item_button(
+                ns_status_item,
+                Icon::with_overlay(icon.as_ref(), self.overlay_icon.as_ref()),
+                false,
+                self.mtm,
+            )?;
             tray_target.update_dimensions();
         }
         self.attrs.icon = icon;
         Ok(())
     }
 
+    pub fn set_overlay_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.overlay_icon = icon;
+        // macOS has no overlays, redraw the icon with the overlay on top
+        self.set_icon_with_as_template(self.attrs.icon.clone(), self.attrs.icon_is_template)
+    }
+
     pub fn set_menu(&mut self, menu: Option<Box<dyn menu::ContextMenu>>) {
         if let (Some(ns_status_item), Some(tray_target)) = (&self.ns_status_item, &self.tray_target)
         {
@@ -140,11 +161,14 @@ impl TrayIcon {
         self.attrs.menu = menu;
     }
 
-    pub fn set_tooltip<S: AsRef<str>>(&mut self, tooltip: Option<S>) -> crate::Result<()> {
-        let tooltip = tooltip.map(|s| s.as_ref().to_string());
+    pub fn set_tooltip(&mut self, tooltip: Option<Tooltip>) -> crate::Result<()> {
         if let (Some(ns_status_item), Some(tray_target)) = (&self.ns_status_item, &self.tray_target)
         {
-            Self::set_tooltip_inner(ns_status_item, tooltip.clone(), self.mtm)?;
+            Self::set_tooltip_inner(
+                ns_status_item,
+                tooltip.as_ref().map(Tooltip::to_text),
+                self.mtm,
+            )?;
             tray_target.update_dimensions();
         }
         self.attrs.tooltip = tooltip;
@@ -192,7 +216,8 @@ impl TrayIcon {
     pub fn set_visible(&mut self, visible: bool) -> crate::Result<()> {
         if visible {
             if self.ns_status_item.is_none() {
-                let (ns_status_item, tray_target) = Self::create(&self.id, &self.attrs, self.mtm)?;
+                let (ns_status_item, tray_target) =
+                    Self::create(&self.id, &self.attrs, self.overlay_icon.as_ref(), self.mtm)?;
                 self.ns_status_item = Some(ns_status_item);
                 self.tray_target = Some(tray_target);
             }
@@ -225,7 +250,7 @@ impl TrayIcon {
         {
             set_icon_for_ns_status_item_button(
                 ns_status_item,
-                icon.clone(),
+                Icon::with_overlay(icon.as_ref(), self.overlay_icon.as_ref()),
                 is_template,
                 self.mtm,
             )?;
@@ -259,6 +284,15 @@ impl Drop for TrayIcon {
     }
 }
 
+/// The menu bar is always there.
+pub fn is_tray_available() -> bool {
+    true
+}
+
+pub fn double_click_interval() -> Duration {
+    Duration::from_secs_f64(unsafe { NSEvent::doubleClickInterval() })
+}
+
 fn set_icon_for_ns_status_item_button(
     ns_status_item: &NSStatusItem,
     icon: Option<Icon>,
@@ -502,6 +536,16 @@ fn get_tray_rect(window: &NSWindow) -> Rect {
     }
 }
 
+fn get_modifiers(event: &NSEvent) -> Modifiers {
+    let flags = unsafe { event.modifierFlags() };
+    Modifiers {
+        shift: flags.contains(NSEventModifierFlags::Shift),
+        control: flags.contains(NSEventModifierFlags::Control),
+        alt: flags.contains(NSEventModifierFlags::Option),
+        super_key: flags.contains(NSEventModifierFlags::Command),
+    }
+}
+
 fn send_mouse_event(
     this: &TrayTarget,
     event: &NSEvent,
@@ -525,6 +569,10 @@ fn send_mouse_event(
         )
         .to_physical(scale_factor);
 
+        // seconds since the system started
+        let time = event.timestamp();
+        let timestamp = EventTimestamp::now().with_platform_time((time * 1000.) as u64);
+
         let event = match mouse_event_type {
             MouseEventType::Click => {
                 let click_event = click_event.unwrap();
@@ -534,22 +582,27 @@ fn send_mouse_event(
                     rect: icon_rect,
                     button: click_event.button,
                     button_state: click_event.state,
+                    modifiers: get_modifiers(event),
+                    timestamp,
                 }
             }
             MouseEventType::Enter => TrayIconEvent::Enter {
                 id: tray_id,
                 position: cursor_position,
                 rect: icon_rect,
+                timestamp,
             },
             MouseEventType::Leave => TrayIconEvent::Leave {
                 id: tray_id,
                 position: cursor_position,
                 rect: icon_rect,
+                timestamp,
             },
             MouseEventType::Move => TrayIconEvent::Move {
                 id: tray_id,
                 position: cursor_position,
                 rect: icon_rect,
+                timestamp,
             },
         };
 
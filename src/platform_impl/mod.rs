@@ -5,11 +5,25 @@
 #[cfg(target_os = "windows")]
 #[path = "windows/mod.rs"]
 mod platform;
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "libappindicator", not(feature = "sni")))]
 #[path = "gtk/mod.rs"]
 mod platform;
+#[cfg(all(target_os = "linux", feature = "sni"))]
+#[path = "sni/mod.rs"]
+mod platform;
 #[cfg(target_os = "macos")]
 #[path = "macos/mod.rs"]
 mod platform;
 
+#[cfg(all(
+    target_os = "linux",
+    not(any(feature = "libappindicator", feature = "sni"))
+))]
+compile_error!("either the `libappindicator` or the `sni` feature must be enabled on Linux");
+
+#[cfg(target_os = "linux")]
+mod gtk_settings;
+
+#[cfg(target_os = "linux")]
+pub(crate) use self::gtk_settings::double_click_interval;
 pub(crate) use self::platform::*;
@@ -0,0 +1,342 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! `com.canonical.dbusmenu` interface, serving a snapshot of the tray menu.
+//!
+//! See <https://github.com/AyatanaIndicators/libdbusmenu/blob/master/libdbusmenu-glib/dbus-menu.xml>
+
+use std::{collections::HashMap, sync::Arc};
+
+use zbus::{
+    fdo, interface,
+    object_server::SignalEmitter,
+    zvariant::{OwnedValue, Value},
+};
+
+/// Object path of the menu on the item connection.
+pub const MENU_PATH: &str = "/MenuBar";
+
+/// Called on `clicked` events, responsible for getting back to the thread owning the menu.
+pub type Activator = Arc<dyn Fn() + Send + Sync>;
+
+/// `(id, properties, children)` where each child is a boxed `Layout` itself.
+pub type Layout = (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+pub type ItemProperties = (i32, HashMap<String, OwnedValue>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Property {
+    Str(String),
+    Bool(bool),
+    Int(i32),
+    Bytes(Vec<u8>),
+    Shortcut(Vec<Vec<String>>),
+}
+
+impl Property {
+    fn to_value(&self) -> OwnedValue {
+        let value = match self {
+            Property::Str(s) => Value::from(s.clone()),
+            Property::Bool(b) => Value::from(*b),
+            Property::Int(i) => Value::from(*i),
+            Property::Bytes(b) => Value::from(b.clone()),
+            Property::Shortcut(s) => Value::from(s.clone()),
+        };
+        // none of the values above carry file descriptors
+        OwnedValue::try_from(value).unwrap()
+    }
+}
+
+/// A single menu entry, `nodes[0]` is always the root.
+#[derive(Clone, Default)]
+pub struct MenuNode {
+    pub properties: Vec<(&'static str, Property)>,
+    pub children: Vec<i32>,
+    pub activate: Option<Activator>,
+}
+
+impl MenuNode {
+    fn properties(&self, names: &[String]) -> HashMap<String, OwnedValue> {
+        self.properties
+            .iter()
+            .filter(|(name, _)| names.is_empty() || names.iter().any(|n| n == name))
+            .map(|(name, value)| (name.to_string(), value.to_value()))
+            .collect()
+    }
+}
+
+/// What changed after [`DBusMenu::update`].
+pub enum MenuUpdate {
+    None,
+    Layout,
+    Properties {
+        updated: Vec<ItemProperties>,
+        removed: Vec<(i32, Vec<String>)>,
+    },
+}
+
+pub struct DBusMenu {
+    pub revision: u32,
+    pub nodes: Vec<MenuNode>,
+}
+
+impl DBusMenu {
+    pub fn new(revision: u32, nodes: Vec<MenuNode>) -> Self {
+        Self { revision, nodes }
+    }
+
+    /// Replaces the snapshot, bumping the revision if the structure changed.
+    pub fn update(&mut self, nodes: Vec<MenuNode>) -> MenuUpdate {
+        let same_layout = self.nodes.len() == nodes.len()
+            && self
+                .nodes
+                .iter()
+                .zip(&nodes)
+                .all(|(old, new)| old.children == new.children);
+
+        if !same_layout {
+            self.revision += 1;
+            self.nodes = nodes;
+            return MenuUpdate::Layout;
+        }
+
+        let mut updated = Vec::new();
+        let mut removed = Vec::new();
+        for (id, (old, new)) in self.nodes.iter().zip(&nodes).enumerate() {
+            let changed: HashMap<String, OwnedValue> = new
+                .properties
+                .iter()
+                .filter(|p| !old.properties.contains(p))
+                .map(|(name, value)| (name.to_string(), value.to_value()))
+                .collect();
+            if !changed.is_empty() {
+                updated.push((id as i32, changed));
+            }
+
+            let gone: Vec<String> = old
+                .properties
+                .iter()
+                .filter(|(name, _)| !new.properties.iter().any(|(n, _)| n == name))
+                .map(|(name, _)| name.to_string())
+                .collect();
+            if !gone.is_empty() {
+                removed.push((id as i32, gone));
+            }
+        }
+
+        self.nodes = nodes;
+
+        if updated.is_empty() && removed.is_empty() {
+            MenuUpdate::None
+        } else {
+            MenuUpdate::Properties { updated, removed }
+        }
+    }
+
+    fn node(&self, id: i32) -> fdo::Result<&MenuNode> {
+        usize::try_from(id)
+            .ok()
+            .and_then(|id| self.nodes.get(id))
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("unknown menu item id {id}")))
+    }
+
+    fn layout(&self, id: i32, depth: i32, names: &[String]) -> Layout {
+        let node = &self.nodes[id as usize];
+        let children = if depth == 0 {
+            Vec::new()
+        } else {
+            node.children
+                .iter()
+                .map(|&child| {
+                    let layout = self.layout(child, depth - 1, names);
+                    OwnedValue::try_from(Value::from(layout)).unwrap()
+                })
+                .collect()
+        };
+
+        (id, node.properties(names), children)
+    }
+
+    fn activate(&self, id: i32) -> fdo::Result<()> {
+        if let Some(activate) = &self.node(id)?.activate {
+            activate();
+        }
+        Ok(())
+    }
+}
+
+#[interface(name = "com.canonical.dbusmenu")]
+impl DBusMenu {
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        recursion_depth: i32,
+        property_names: Vec<String>,
+    ) -> fdo::Result<(u32, Layout)> {
+        self.node(parent_id)?;
+        Ok((
+            self.revision,
+            self.layout(parent_id, recursion_depth, &property_names),
+        ))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        property_names: Vec<String>,
+    ) -> Vec<ItemProperties> {
+        ids.into_iter()
+            .filter_map(|id| {
+                self.node(id)
+                    .ok()
+                    .map(|node| (id, node.properties(&property_names)))
+            })
+            .collect()
+    }
+
+    fn get_property(&self, id: i32, name: &str) -> fdo::Result<OwnedValue> {
+        self.node(id)?
+            .properties
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value.to_value())
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("unknown property {name}")))
+    }
+
+    fn event(&self, id: i32, event_id: &str, _data: Value<'_>, _timestamp: u32) -> fdo::Result<()> {
+        if event_id == "clicked" {
+            self.activate(id)?;
+        }
+        Ok(())
+    }
+
+    fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+        events
+            .into_iter()
+            .filter_map(|(id, event_id, _, _)| {
+                if event_id == "clicked" {
+                    self.activate(id).err().map(|_| id)
+                } else {
+                    self.node(id).err().map(|_| id)
+                }
+            })
+            .collect()
+    }
+
+    fn about_to_show(&self, id: i32) -> fdo::Result<bool> {
+        // the snapshot is kept up to date, hosts never need to refetch
+        self.node(id).map(|_| false)
+    }
+
+    fn about_to_show_group(&self, ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        let errors = ids
+            .into_iter()
+            .filter(|&id| self.node(id).is_err())
+            .collect();
+        (Vec::new(), errors)
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> &str {
+        "ltr"
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        "normal"
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(signal)]
+    pub async fn items_properties_updated(
+        emitter: &SignalEmitter<'_>,
+        updated_props: &[ItemProperties],
+        removed_props: &[(i32, Vec<String>)],
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn layout_updated(
+        emitter: &SignalEmitter<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn label(text: &str) -> MenuNode {
+        MenuNode {
+            properties: vec![("label", Property::Str(text.to_string()))],
+            ..Default::default()
+        }
+    }
+
+    fn menu() -> Vec<MenuNode> {
+        let root = MenuNode {
+            children: vec![1, 2],
+            ..Default::default()
+        };
+        vec![root, label("a"), label("b")]
+    }
+
+    #[test]
+    fn update_diffs_snapshots() {
+        let mut dbus_menu = DBusMenu::new(1, menu());
+
+        assert!(matches!(dbus_menu.update(menu()), MenuUpdate::None));
+
+        let mut nodes = menu();
+        nodes[2].properties = vec![("enabled", Property::Bool(false))];
+        match dbus_menu.update(nodes) {
+            MenuUpdate::Properties { updated, removed } => {
+                assert_eq!(updated.len(), 1);
+                assert_eq!(updated[0].0, 2);
+                assert!(updated[0].1.contains_key("enabled"));
+                assert_eq!(removed, vec![(2, vec!["label".to_string()])]);
+            }
+            _ => panic!("expected a properties update"),
+        }
+        assert_eq!(dbus_menu.revision, 1);
+
+        let mut nodes = menu();
+        nodes[0].children.pop();
+        nodes.pop();
+        assert!(matches!(dbus_menu.update(nodes), MenuUpdate::Layout));
+        assert_eq!(dbus_menu.revision, 2);
+    }
+
+    #[test]
+    fn layout_respects_depth_and_activates() {
+        let clicks = Arc::new(AtomicUsize::new(0));
+        let mut nodes = menu();
+        let c = clicks.clone();
+        nodes[1].activate = Some(Arc::new(move || {
+            c.fetch_add(1, Ordering::Relaxed);
+        }));
+        let dbus_menu = DBusMenu::new(1, nodes);
+
+        let (id, _, children) = dbus_menu.layout(0, -1, &[]);
+        assert_eq!((id, children.len()), (0, 2));
+        let (_, _, children) = dbus_menu.layout(0, 0, &[]);
+        assert!(children.is_empty());
+
+        dbus_menu.activate(1).unwrap();
+        dbus_menu.activate(2).unwrap();
+        assert!(dbus_menu.activate(7).is_err());
+        assert_eq!(clicks.load(Ordering::Relaxed), 1);
+    }
+}
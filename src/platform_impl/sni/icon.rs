@@ -0,0 +1,143 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use crate::icon::{BadIcon, RgbaIcon, PIXEL_SIZE};
+
+use super::item::Pixmap;
+
+/// Sizes commonly used by tray hosts, they pick the pixmap closest to the size they need.
+const PIXMAP_SIZES: &[u32] = &[64, 48, 32, 24, 22, 16];
+
+#[derive(Debug, Clone)]
+pub struct PlatformIcon(RgbaIcon);
+
+impl PlatformIcon {
+    pub fn from_rgba(rgba: Vec<u8>, width: u32, height: u32) -> Result<Self, BadIcon> {
+        Ok(PlatformIcon(RgbaIcon::from_rgba(rgba, width, height)?))
+    }
+
+    pub fn to_rgba(&self) -> Option<RgbaIcon> {
+        Some(self.0.clone())
+    }
+
+    /// Converts the icon into the pixmaps sent over D-Bus, the original size
+    /// followed by smaller versions so hosts don't have to scale it themselves.
+    pub fn to_pixmaps(&self) -> Vec<Pixmap> {
+        let mut pixmaps = vec![to_pixmap(&self.0)];
+        // an empty icon has nothing to scale
+        if self.0.width == 0 || self.0.height == 0 {
+            return pixmaps;
+        }
+
+        let largest = self.0.width.max(self.0.height);
+        for &size in PIXMAP_SIZES.iter().filter(|&&size| size < largest) {
+            pixmaps.push(to_pixmap(&downscale(&self.0, size, largest)));
+        }
+        pixmaps
+    }
+}
+
+/// Converts the icon into the `(width, height, data)` pixmap used by the
+/// StatusNotifierItem spec, where `data` is ARGB32 in network byte order.
+fn to_pixmap(icon: &RgbaIcon) -> Pixmap {
+    let mut argb = icon.rgba.clone();
+    for pixel in argb.chunks_exact_mut(PIXEL_SIZE) {
+        // RGBA -> ARGB
+        pixel.rotate_right(1);
+    }
+
+    (icon.width as i32, icon.height as i32, argb)
+}
+
+/// Scales the icon so its largest side is `size`, averaging the pixels each
+/// destination pixel covers. Colors are weighted by alpha to avoid dark edges.
+pub fn downscale(icon: &RgbaIcon, size: u32, largest: u32) -> RgbaIcon {
+    let width = (icon.width * size / largest).max(1);
+    let height = (icon.height * size / largest).max(1);
+
+    let mut rgba = Vec::with_capacity((width * height) as usize * PIXEL_SIZE);
+    for y in 0..height {
+        let (y0, y1) = source_range(y, height, icon.height);
+        for x in 0..width {
+            let (x0, x1) = source_range(x, width, icon.width);
+
+            let mut sum = [0u64; 4];
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let i = ((sy * icon.width + sx) as usize) * PIXEL_SIZE;
+                    let pixel = &icon.rgba[i..i + PIXEL_SIZE];
+                    let alpha = pixel[3] as u64;
+                    for (sum, &c) in sum.iter_mut().zip(&pixel[..3]) {
+                        *sum += c as u64 * alpha;
+                    }
+                    sum[3] += alpha;
+                }
+            }
+
+            let count = ((x1 - x0) * (y1 - y0)) as u64;
+            if sum[3] == 0 {
+                rgba.extend_from_slice(&[0; PIXEL_SIZE]);
+            } else {
+                rgba.extend(sum[..3].iter().map(|c| (c / sum[3]) as u8));
+                rgba.push((sum[3] / count) as u8);
+            }
+        }
+    }
+
+    RgbaIcon {
+        rgba,
+        width,
+        height,
+    }
+}
+
+/// Source pixels `start..end` covered by the destination pixel `i`.
+fn source_range(i: u32, dst: u32, src: u32) -> (u32, u32) {
+    let start = i * src / dst;
+    let end = ((i + 1) * src / dst).max(start + 1);
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixmaps_include_smaller_sizes() {
+        let icon = PlatformIcon::from_rgba([255, 0, 0, 255].repeat(32 * 16), 32, 16).unwrap();
+
+        let sizes: Vec<_> = icon
+            .to_pixmaps()
+            .iter()
+            .map(|(width, height, data)| {
+                assert_eq!(data.len(), (width * height * 4) as usize);
+                (*width, *height)
+            })
+            .collect();
+        assert_eq!(sizes, [(32, 16), (24, 12), (22, 11), (16, 8)]);
+    }
+
+    #[test]
+    fn empty_icons_have_a_single_pixmap() {
+        for (width, height) in [(0, 0), (0, 100), (100, 0)] {
+            let icon = PlatformIcon::from_rgba(Vec::new(), width, height).unwrap();
+            assert_eq!(
+                icon.to_pixmaps(),
+                [(width as i32, height as i32, Vec::new())]
+            );
+        }
+    }
+
+    #[test]
+    fn downscale_averages_by_alpha() {
+        // opaque red next to transparent green, the green must not bleed in
+        let mut rgba = [255, 0, 0, 255, 0, 255, 0, 0].repeat(2);
+        rgba.extend([0, 0, 255, 255].repeat(4));
+        let icon = RgbaIcon::from_rgba(rgba, 2, 4).unwrap();
+
+        let scaled = downscale(&icon, 2, 4);
+        assert_eq!((scaled.width, scaled.height), (1, 2));
+        assert_eq!(scaled.rgba, [255, 0, 0, 127, 0, 0, 255, 255]);
+    }
+}
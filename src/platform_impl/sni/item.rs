@@ -0,0 +1,248 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! `org.kde.StatusNotifierItem` interface exported for each tray icon and the
+//! `org.kde.StatusNotifierWatcher` proxy used to register it.
+//!
+//! See <https://www.freedesktop.org/wiki/Specifications/StatusNotifierItem/StatusNotifierItem/>
+
+use zbus::{fdo, interface, object_server::SignalEmitter, proxy, zvariant::OwnedObjectPath};
+
+use super::dbusmenu::MENU_PATH;
+use crate::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    EventTimestamp, Modifiers, MouseButton, MouseButtonState, Rect, ScrollOrientation, Tooltip,
+    TrayCategory, TrayIconEvent, TrayIconId, TrayStatus,
+};
+
+/// An icon pixmap as `(width, height, ARGB32 big-endian data)`.
+pub type Pixmap = (i32, i32, Vec<u8>);
+
+/// `(icon name, icon pixmaps, title, description)`.
+pub type ToolTip = (String, Vec<Pixmap>, String, String);
+
+/// Object path used for the item on its own connection.
+pub const ITEM_PATH: &str = "/StatusNotifierItem";
+
+/// Object path advertised in the `Menu` property when there is no menu.
+pub const NO_MENU_PATH: &str = "/NO_DBUSMENU";
+
+pub struct StatusNotifierItem {
+    pub tray_id: TrayIconId,
+    pub id: String,
+    pub category: TrayCategory,
+    pub title: String,
+    pub tooltip: Option<Tooltip>,
+    pub status: TrayStatus,
+    pub icon_pixmap: Vec<Pixmap>,
+    pub overlay_icon_pixmap: Vec<Pixmap>,
+    pub attention_icon_pixmap: Vec<Pixmap>,
+    pub attention_movie_name: String,
+    pub has_menu: bool,
+    /// Where the item was last activated.
+    pub last_rect: Option<Rect>,
+}
+
+impl StatusNotifierItem {
+    /// Hosts only tell us about activations, so report them as a full down/up click.
+    ///
+    /// The coordinates are the only geometry hosts give, usually the cursor or a
+    /// corner of the icon, so they are kept as a zero-sized rect.
+    fn send_click(&mut self, button: MouseButton, x: i32, y: i32) {
+        let position = PhysicalPosition::new(x as f64, y as f64);
+        let rect = Rect {
+            size: PhysicalSize::new(0, 0),
+            position,
+        };
+        self.last_rect = Some(rect);
+
+        for button_state in [MouseButtonState::Down, MouseButtonState::Up] {
+            TrayIconEvent::send(TrayIconEvent::Click {
+                id: self.tray_id.clone(),
+                position,
+                rect,
+                button,
+                button_state,
+                // not forwarded by hosts
+                modifiers: Modifiers::default(),
+                timestamp: EventTimestamp::now(),
+            });
+        }
+    }
+}
+
+#[interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    fn activate(&mut self, x: i32, y: i32) {
+        self.send_click(MouseButton::Left, x, y);
+    }
+
+    fn secondary_activate(&mut self, x: i32, y: i32) {
+        self.send_click(MouseButton::Middle, x, y);
+    }
+
+    fn context_menu(&mut self, x: i32, y: i32) {
+        self.send_click(MouseButton::Right, x, y);
+    }
+
+    fn scroll(&self, delta: i32, orientation: &str) -> fdo::Result<()> {
+        // the spec says lowercase but some hosts send `Vertical`
+        let orientation = match orientation.to_ascii_lowercase().as_str() {
+            "vertical" => ScrollOrientation::Vertical,
+            "horizontal" => ScrollOrientation::Horizontal,
+            _ => {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "unknown orientation {orientation}"
+                )))
+            }
+        };
+
+        TrayIconEvent::send(TrayIconEvent::Scroll {
+            id: self.tray_id.clone(),
+            delta,
+            orientation,
+            position: PhysicalPosition::default(),
+            rect: self.last_rect.unwrap_or_default(),
+            timestamp: EventTimestamp::now(),
+        });
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn category(&self) -> &str {
+        match self.category {
+            TrayCategory::ApplicationStatus => "ApplicationStatus",
+            TrayCategory::Communications => "Communications",
+            TrayCategory::SystemServices => "SystemServices",
+            TrayCategory::Hardware => "Hardware",
+        }
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        status_name(self.status)
+    }
+
+    #[zbus(property)]
+    fn window_id(&self) -> i32 {
+        0
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn icon_pixmap(&self) -> Vec<Pixmap> {
+        self.icon_pixmap.clone()
+    }
+
+    #[zbus(property)]
+    fn overlay_icon_name(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn overlay_icon_pixmap(&self) -> Vec<Pixmap> {
+        self.overlay_icon_pixmap.clone()
+    }
+
+    #[zbus(property)]
+    fn attention_icon_name(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn attention_icon_pixmap(&self) -> Vec<Pixmap> {
+        self.attention_icon_pixmap.clone()
+    }
+
+    #[zbus(property)]
+    fn attention_movie_name(&self) -> &str {
+        &self.attention_movie_name
+    }
+
+    #[zbus(property)]
+    fn tool_tip(&self) -> ToolTip {
+        let Some(tooltip) = &self.tooltip else {
+            return Default::default();
+        };
+        (
+            String::new(),
+            tooltip
+                .icon
+                .iter()
+                .flat_map(|icon| icon.inner.to_pixmaps())
+                .collect(),
+            tooltip.title.clone(),
+            tooltip.body.clone(),
+        )
+    }
+
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        let path = if self.has_menu {
+            MENU_PATH
+        } else {
+            NO_MENU_PATH
+        };
+        OwnedObjectPath::try_from(path).unwrap()
+    }
+
+    #[zbus(signal)]
+    pub async fn new_title(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn new_icon(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn new_overlay_icon(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn new_attention_icon(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn new_tool_tip(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn new_status(emitter: &SignalEmitter<'_>, status: &str) -> zbus::Result<()>;
+}
+
+pub fn status_name(status: TrayStatus) -> &'static str {
+    match status {
+        TrayStatus::Passive => "Passive",
+        TrayStatus::Active => "Active",
+        TrayStatus::NeedsAttention => "NeedsAttention",
+    }
+}
+
+#[proxy(
+    interface = "org.kde.StatusNotifierWatcher",
+    default_service = "org.kde.StatusNotifierWatcher",
+    default_path = "/StatusNotifierWatcher"
+)]
+pub trait StatusNotifierWatcher {
+    fn register_status_notifier_item(&self, service: &str) -> zbus::Result<()>;
+}
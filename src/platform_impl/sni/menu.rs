@@ -0,0 +1,311 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Exports muda menus over D-Bus.
+//!
+//! The layout is read from the gtk menu muda builds for context menus rather than from the
+//! muda items themselves: [`muda::ContextMenu`] only gives access to that gtk menu, the items
+//! don't expose their icon or accelerator, and muda doesn't notify about changes made to
+//! them. [`muda::MenuEvent`]s can only be emitted by activating the gtk items as well, so
+//! the default [`glib::MainContext`] must be running for clicks to be reported.
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::{Rc, Weak},
+    sync::Arc,
+};
+
+use gtk::{gdk, glib, prelude::*};
+use zbus::blocking::{object_server::InterfaceRef, Connection};
+
+use super::dbusmenu::{DBusMenu, MenuNode, MenuUpdate, Property, MENU_PATH};
+
+/// Properties whose change on a gtk widget means the exported menu needs a refresh.
+const WATCHED_PROPERTIES: &[&str] = &["label", "sensitive", "visible", "active", "pixbuf"];
+
+/// Exports the gtk context menu of a [`muda::ContextMenu`] as `com.canonical.dbusmenu`
+/// and keeps it in sync with the changes made to it.
+///
+/// muda items live on the gtk thread so the snapshot is taken there and `clicked`
+/// events are dispatched back to the default [`glib::MainContext`].
+pub struct MenuExporter {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    gtk_menu: gtk::Menu,
+    dbus_menu: InterfaceRef<DBusMenu>,
+    connection: Connection,
+    handlers: RefCell<Vec<(glib::Object, glib::SignalHandlerId)>>,
+    refresh_scheduled: Cell<bool>,
+}
+
+impl MenuExporter {
+    pub fn new(connection: &Connection, gtk_menu: gtk::Menu, revision: u32) -> zbus::Result<Self> {
+        let object_server = connection.object_server();
+        object_server.at(MENU_PATH, DBusMenu::new(revision, snapshot(&gtk_menu)))?;
+        let dbus_menu = object_server.interface::<_, DBusMenu>(MENU_PATH)?;
+
+        // hosts may still have a layout of a previous menu cached
+        zbus::block_on(DBusMenu::layout_updated(
+            dbus_menu.signal_emitter(),
+            revision,
+            0,
+        ))?;
+
+        let inner = Rc::new(Inner {
+            gtk_menu,
+            dbus_menu,
+            connection: connection.clone(),
+            handlers: RefCell::default(),
+            refresh_scheduled: Cell::new(false),
+        });
+        inner.watch(&Rc::downgrade(&inner));
+
+        Ok(Self { inner })
+    }
+
+    pub fn revision(&self) -> u32 {
+        self.inner.dbus_menu.get().revision
+    }
+
+    #[cfg(feature = "xembed")]
+    pub fn gtk_menu(&self) -> &gtk::Menu {
+        &self.inner.gtk_menu
+    }
+}
+
+impl Drop for MenuExporter {
+    fn drop(&mut self) {
+        self.inner.unwatch();
+        let _ = self
+            .inner
+            .connection
+            .object_server()
+            .remove::<DBusMenu, _>(MENU_PATH);
+    }
+}
+
+impl Inner {
+    /// Connects to every menu shell and item so edits made through muda are picked up.
+    fn watch(&self, this: &Weak<Inner>) {
+        let mut handlers = self.handlers.borrow_mut();
+        watch_menu(&self.gtk_menu, this, &mut handlers);
+    }
+
+    fn unwatch(&self) {
+        for (object, handler) in self.handlers.borrow_mut().drain(..) {
+            object.disconnect(handler);
+        }
+    }
+
+    /// Coalesces bursts of changes, muda usually touches several widgets at once.
+    fn schedule_refresh(this: &Weak<Inner>) {
+        let Some(inner) = this.upgrade() else {
+            return;
+        };
+        if inner.refresh_scheduled.replace(true) {
+            return;
+        }
+
+        let this = this.clone();
+        glib::idle_add_local_once(move || {
+            if let Some(inner) = this.upgrade() {
+                inner.refresh_scheduled.set(false);
+                inner.refresh(&this);
+            }
+        });
+    }
+
+    fn refresh(&self, this: &Weak<Inner>) {
+        // items may have been added or removed, reconnect everything
+        self.unwatch();
+        self.watch(this);
+
+        let update = self.dbus_menu.get_mut().update(snapshot(&self.gtk_menu));
+        let emitter = self.dbus_menu.signal_emitter();
+        let _ = match update {
+            MenuUpdate::None => Ok(()),
+            MenuUpdate::Layout => {
+                let revision = self.dbus_menu.get().revision;
+                zbus::block_on(DBusMenu::layout_updated(emitter, revision, 0))
+            }
+            MenuUpdate::Properties { updated, removed } => zbus::block_on(
+                DBusMenu::items_properties_updated(emitter, &updated, &removed),
+            ),
+        };
+    }
+}
+
+fn watch_menu(
+    menu: &gtk::Menu,
+    this: &Weak<Inner>,
+    handlers: &mut Vec<(glib::Object, glib::SignalHandlerId)>,
+) {
+    let weak = this.clone();
+    let handler = menu.connect_insert(move |_, _, _| Inner::schedule_refresh(&weak));
+    handlers.push((menu.clone().upcast(), handler));
+
+    let weak = this.clone();
+    let handler = menu.connect_remove(move |_, _| Inner::schedule_refresh(&weak));
+    handlers.push((menu.clone().upcast(), handler));
+
+    for item in menu_items(menu) {
+        watch_widget(item.upcast_ref(), this, handlers);
+
+        if let Some(submenu) = submenu(&item) {
+            watch_menu(&submenu, this, handlers);
+        }
+    }
+}
+
+/// Watches a menu item and the widgets it is made of, i.e. the label and image of icon items.
+fn watch_widget(
+    widget: &gtk::Widget,
+    this: &Weak<Inner>,
+    handlers: &mut Vec<(glib::Object, glib::SignalHandlerId)>,
+) {
+    let weak = this.clone();
+    let handler = widget.connect_notify_local(None, move |_, pspec| {
+        if WATCHED_PROPERTIES.contains(&pspec.name()) {
+            Inner::schedule_refresh(&weak);
+        }
+    });
+    handlers.push((widget.clone().upcast(), handler));
+
+    if widget.is::<gtk::MenuItem>() || widget.is::<gtk::Box>() {
+        if let Some(container) = widget.downcast_ref::<gtk::Container>() {
+            for child in container.children() {
+                watch_widget(&child, this, handlers);
+            }
+        }
+    }
+}
+
+fn menu_items(menu: &gtk::Menu) -> impl Iterator<Item = gtk::MenuItem> {
+    menu.children()
+        .into_iter()
+        .filter_map(|child| child.downcast::<gtk::MenuItem>().ok())
+}
+
+fn submenu(item: &gtk::MenuItem) -> Option<gtk::Menu> {
+    item.submenu()
+        .and_then(|submenu| submenu.downcast::<gtk::Menu>().ok())
+}
+
+/// Walks the gtk menu, the root gets id `0` and items are numbered depth-first.
+fn snapshot(menu: &gtk::Menu) -> Vec<MenuNode> {
+    let mut nodes = vec![MenuNode {
+        properties: vec![("children-display", Property::Str("submenu".into()))],
+        ..Default::default()
+    }];
+    append_items(menu, 0, &mut nodes);
+    nodes
+}
+
+fn append_items(menu: &gtk::Menu, parent: usize, nodes: &mut Vec<MenuNode>) {
+    for item in menu_items(menu) {
+        let id = nodes.len();
+        nodes.push(item_node(&item));
+        nodes[parent].children.push(id as i32);
+
+        if let Some(submenu) = submenu(&item) {
+            nodes[id]
+                .properties
+                .push(("children-display", Property::Str("submenu".into())));
+            append_items(&submenu, id, nodes);
+        }
+    }
+}
+
+fn item_node(item: &gtk::MenuItem) -> MenuNode {
+    let mut properties = Vec::new();
+
+    if !item.is_visible() {
+        properties.push(("visible", Property::Bool(false)));
+    }
+
+    if item.is::<gtk::SeparatorMenuItem>() {
+        properties.push(("type", Property::Str("separator".into())));
+        return MenuNode {
+            properties,
+            ..Default::default()
+        };
+    }
+
+    if let Some(label) = find_child::<gtk::Label>(item.upcast_ref()) {
+        // both gtk and dbusmenu use `_` for mnemonics
+        properties.push(("label", Property::Str(label.label().into())));
+    }
+
+    if !item.is_sensitive() {
+        properties.push(("enabled", Property::Bool(false)));
+    }
+
+    if let Some(check) = item.downcast_ref::<gtk::CheckMenuItem>() {
+        properties.push(("toggle-type", Property::Str("checkmark".into())));
+        properties.push(("toggle-state", Property::Int(check.is_active() as i32)));
+    }
+
+    if let Some(png) = find_child::<gtk::Image>(item.upcast_ref())
+        .and_then(|image| image.pixbuf())
+        .and_then(|pixbuf| pixbuf.save_to_bufferv("png", &[]).ok())
+    {
+        properties.push(("icon-data", Property::Bytes(png)));
+    }
+
+    if let Some(shortcut) = find_child::<gtk::AccelLabel>(item.upcast_ref()).and_then(|label| {
+        let (key, mods) = label.accel();
+        shortcut(key, mods)
+    }) {
+        properties.push(("shortcut", Property::Shortcut(vec![shortcut])));
+    }
+
+    let item = glib::SendWeakRef::from(item.downgrade());
+    MenuNode {
+        properties,
+        children: Vec::new(),
+        activate: Some(Arc::new(move || {
+            let item = item.clone();
+            glib::MainContext::default().spawn(async move {
+                if let Some(item) = item.upgrade() {
+                    item.activate();
+                }
+            });
+        })),
+    }
+}
+
+fn find_child<T: IsA<gtk::Widget>>(widget: &gtk::Widget) -> Option<T> {
+    widget
+        .downcast_ref::<gtk::Container>()?
+        .children()
+        .into_iter()
+        .find_map(|child| {
+            child
+                .clone()
+                .downcast::<T>()
+                .ok()
+                .or_else(|| find_child(&child))
+        })
+}
+
+fn shortcut(key: u32, mods: gdk::ModifierType) -> Option<Vec<String>> {
+    let name = gdk::keys::Key::from(key).name()?;
+
+    let mut shortcut = Vec::new();
+    for (modifier, name) in [
+        (gdk::ModifierType::CONTROL_MASK, "Control"),
+        (gdk::ModifierType::MOD1_MASK, "Alt"),
+        (gdk::ModifierType::SHIFT_MASK, "Shift"),
+        (gdk::ModifierType::SUPER_MASK, "Super"),
+    ] {
+        if mods.contains(modifier) {
+            shortcut.push(name.to_string());
+        }
+    }
+    shortcut.push(name.into());
+
+    Some(shortcut)
+}
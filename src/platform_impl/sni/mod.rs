@@ -0,0 +1,865 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+mod dbusmenu;
+mod icon;
+mod item;
+mod menu;
+#[cfg(feature = "xembed")]
+mod xembed;
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use zbus::{
+    blocking::{connection, fdo::DBusProxy, object_server::InterfaceRef, Connection},
+    names::BusName,
+};
+
+pub(crate) use self::icon::PlatformIcon;
+use self::item::{
+    status_name, Pixmap, StatusNotifierItem, StatusNotifierWatcherProxyBlocking, ITEM_PATH,
+};
+use self::menu::MenuExporter;
+#[cfg(feature = "xembed")]
+use self::xembed::XEmbedTray;
+use crate::{
+    icon::Icon, EventTimestamp, Tooltip, TrayIconAttributes, TrayIconEvent, TrayIconId, TrayStatus,
+    COUNTER,
+};
+
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+
+pub struct TrayIcon {
+    connection: Connection,
+    name: String,
+    menu: Option<Box<dyn muda::ContextMenu>>,
+    menu_exporter: Option<MenuExporter>,
+    icon: Option<Icon>,
+    overlay_icon: Option<Icon>,
+    composite_overlay: bool,
+    #[cfg(feature = "xembed")]
+    xembed: Option<Arc<XEmbedTray>>,
+}
+
+impl TrayIcon {
+    pub fn new(id: TrayIconId, attrs: TrayIconAttributes) -> crate::Result<Self> {
+        Self::with_builder(connection::Builder::session()?, id, attrs)
+    }
+
+    fn with_builder(
+        builder: connection::Builder<'_>,
+        id: TrayIconId,
+        attrs: TrayIconAttributes,
+    ) -> crate::Result<Self> {
+        let name = format!(
+            "org.kde.StatusNotifierItem-{}-{}",
+            std::process::id(),
+            COUNTER.next()
+        );
+
+        let availability_id = id.clone();
+        #[cfg(feature = "xembed")]
+        let (xembed_id, xembed_icon) = (id.clone(), attrs.icon.clone());
+
+        let item = StatusNotifierItem {
+            id: attrs.app_id_or_default(&id),
+            category: attrs.category,
+            tray_id: id,
+            title: attrs.title.unwrap_or_default(),
+            tooltip: attrs.tooltip,
+            status: TrayStatus::Active,
+            icon_pixmap: attrs
+                .icon
+                .as_ref()
+                .map(|icon| icon.inner.to_pixmaps())
+                .unwrap_or_default(),
+            overlay_icon_pixmap: Vec::new(),
+            attention_icon_pixmap: Vec::new(),
+            attention_movie_name: String::new(),
+            has_menu: attrs.menu.is_some(),
+            last_rect: None,
+        };
+
+        let connection = builder
+            .name(name.clone())?
+            .serve_at(ITEM_PATH, item)?
+            .build()?;
+
+        let menu_exporter = attrs
+            .menu
+            .as_ref()
+            .map(|menu| MenuExporter::new(&connection, menu.gtk_context_menu(), 1))
+            .transpose()?;
+
+        // subscribe first so a watcher starting right after registering isn't missed
+        let watcher = StatusNotifierWatcherProxyBlocking::new(&connection)?;
+        let watcher_changes = watcher.inner().receive_owner_changed()?;
+
+        // The watcher may not be running, the item is still exported
+        // and registered once it starts.
+        let registered = watcher.register_status_notifier_item(&name).is_ok();
+        let availability = Arc::new(HostAvailability {
+            id: availability_id,
+            hosts: Mutex::new(Hosts {
+                watcher: registered,
+                xembed: false,
+            }),
+        });
+
+        // docked in the X11 system tray while there is no watcher
+        #[cfg(feature = "xembed")]
+        let xembed = XEmbedTray::new(
+            None,
+            xembed_id,
+            xembed_icon,
+            menu_exporter.as_ref().map(MenuExporter::gtk_menu),
+            !registered,
+            {
+                let availability = availability.clone();
+                move |running| availability.set_xembed(running)
+            },
+        )
+        .ok()
+        .map(Arc::new);
+        #[cfg(feature = "xembed")]
+        if let Some(xembed) = &xembed {
+            availability.hosts.lock().unwrap().xembed = xembed.has_tray();
+        }
+
+        #[cfg(feature = "xembed")]
+        let weak_xembed = xembed.as_ref().map(Arc::downgrade);
+        let service = name.clone();
+        // The watcher goes away when the tray host restarts and forgets every
+        // item, the state is kept in the exported item so registering it again
+        // is enough. Ends once the connection is closed.
+        std::thread::Builder::new()
+            .name("tray-icon-sni-watcher".into())
+            .spawn(move || {
+                for owner in watcher_changes {
+                    let registered =
+                        owner.is_some() && watcher.register_status_notifier_item(&service).is_ok();
+                    availability.set_watcher(registered);
+
+                    #[cfg(feature = "xembed")]
+                    if let Some(xembed) = weak_xembed.as_ref().and_then(|x| x.upgrade()) {
+                        xembed.set_enabled(!registered);
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            connection,
+            name,
+            menu: attrs.menu,
+            menu_exporter,
+            icon: attrs.icon,
+            overlay_icon: None,
+            composite_overlay: attrs.composite_overlay,
+            #[cfg(feature = "xembed")]
+            xembed,
+        })
+    }
+
+    fn item(&self) -> zbus::Result<InterfaceRef<StatusNotifierItem>> {
+        self.connection
+            .object_server()
+            .interface::<_, StatusNotifierItem>(ITEM_PATH)
+    }
+
+    /// The `IconPixmap` of the item, with the overlay drawn in when compositing it.
+    fn icon_pixmaps(&self) -> Vec<Pixmap> {
+        let overlay = self
+            .overlay_icon
+            .as_ref()
+            .filter(|_| self.composite_overlay);
+        Icon::with_overlay(self.icon.as_ref(), overlay)
+            .map(|icon| icon.inner.to_pixmaps())
+            .unwrap_or_default()
+    }
+
+    pub fn set_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        #[cfg(feature = "xembed")]
+        if let Some(xembed) = &self.xembed {
+            xembed.set_icon(icon.clone());
+        }
+
+        self.icon = icon;
+        let item = self.item()?;
+        item.get_mut().icon_pixmap = self.icon_pixmaps();
+        zbus::block_on(StatusNotifierItem::new_icon(item.signal_emitter()))?;
+        Ok(())
+    }
+
+    pub fn set_overlay_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        #[cfg(feature = "xembed")]
+        if let Some(xembed) = &self.xembed {
+            xembed.set_overlay_icon(icon.clone());
+        }
+
+        self.overlay_icon = icon;
+        let item = self.item()?;
+        if self.composite_overlay {
+            item.get_mut().icon_pixmap = self.icon_pixmaps();
+            zbus::block_on(StatusNotifierItem::new_icon(item.signal_emitter()))?;
+        } else {
+            item.get_mut().overlay_icon_pixmap = self
+                .overlay_icon
+                .as_ref()
+                .map(|icon| icon.inner.to_pixmaps())
+                .unwrap_or_default();
+            zbus::block_on(StatusNotifierItem::new_overlay_icon(item.signal_emitter()))?;
+        }
+        Ok(())
+    }
+
+    pub fn set_menu(&mut self, menu: Option<Box<dyn crate::menu::ContextMenu>>) {
+        // drop the previous exporter first so the new one can take over its object path
+        let revision = self
+            .menu_exporter
+            .take()
+            .map(|exporter| exporter.revision() + 1)
+            .unwrap_or(1);
+
+        // without an exporter, the `Menu` property below points hosts to no menu
+        self.menu_exporter = menu.as_ref().and_then(|menu| {
+            MenuExporter::new(&self.connection, menu.gtk_context_menu(), revision)
+                .map_err(|e| eprintln!("Error exporting the tray icon menu: {e}"))
+                .ok()
+        });
+
+        #[cfg(feature = "xembed")]
+        if let Some(xembed) = &self.xembed {
+            xembed.set_menu(self.menu_exporter.as_ref().map(MenuExporter::gtk_menu));
+        }
+
+        // hosts cache the menu path, tell them it changed
+        if let Ok(item) = self.item() {
+            item.get_mut().has_menu = self.menu_exporter.is_some();
+            let _ = zbus::block_on(item.get().menu_changed(item.signal_emitter()));
+        }
+
+        self.menu = menu;
+    }
+
+    pub fn set_tooltip(&mut self, tooltip: Option<Tooltip>) -> crate::Result<()> {
+        let item = self.item()?;
+        item.get_mut().tooltip = tooltip;
+        zbus::block_on(StatusNotifierItem::new_tool_tip(item.signal_emitter()))?;
+        Ok(())
+    }
+
+    pub fn set_title<S: AsRef<str>>(&mut self, title: Option<S>) {
+        if let Ok(item) = self.item() {
+            item.get_mut().title = title.map(|t| t.as_ref().to_string()).unwrap_or_default();
+            let _ = zbus::block_on(StatusNotifierItem::new_title(item.signal_emitter()));
+        }
+    }
+
+    pub fn set_visible(&mut self, visible: bool) -> crate::Result<()> {
+        self.set_status(if visible {
+            TrayStatus::Active
+        } else {
+            TrayStatus::Passive
+        })
+    }
+
+    pub fn set_status(&mut self, status: TrayStatus) -> crate::Result<()> {
+        #[cfg(feature = "xembed")]
+        if let Some(xembed) = &self.xembed {
+            xembed.set_status(status);
+        }
+
+        let item = self.item()?;
+        item.get_mut().status = status;
+        zbus::block_on(StatusNotifierItem::new_status(
+            item.signal_emitter(),
+            status_name(status),
+        ))?;
+
+        Ok(())
+    }
+
+    pub fn set_attention_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        #[cfg(feature = "xembed")]
+        if let Some(xembed) = &self.xembed {
+            xembed.set_attention_icon(icon.clone());
+        }
+
+        let item = self.item()?;
+        item.get_mut().attention_icon_pixmap =
+            icon.map(|icon| icon.inner.to_pixmaps()).unwrap_or_default();
+        zbus::block_on(StatusNotifierItem::new_attention_icon(
+            item.signal_emitter(),
+        ))?;
+        Ok(())
+    }
+
+    pub fn set_attention_movie_name<S: AsRef<str>>(
+        &mut self,
+        name: Option<S>,
+    ) -> crate::Result<()> {
+        let item = self.item()?;
+        item.get_mut().attention_movie_name =
+            name.map(|n| n.as_ref().to_string()).unwrap_or_default();
+        zbus::block_on(StatusNotifierItem::new_attention_icon(
+            item.signal_emitter(),
+        ))?;
+        Ok(())
+    }
+
+    pub fn set_temp_dir_path<P: AsRef<Path>>(&mut self, _path: Option<P>) {}
+
+    pub fn rect(&self) -> Option<crate::Rect> {
+        #[cfg(feature = "xembed")]
+        if let Some(rect) = self.xembed.as_ref().and_then(|xembed| xembed.rect()) {
+            return Some(rect);
+        }
+
+        let item = self.item().ok()?;
+        let rect = item.get().last_rect;
+        rect
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        // the watcher unregisters the item once its name is gone, closing
+        // the connection also stops following the watcher
+        let _ = self.connection.release_name(self.name.as_str());
+        let _ = self.connection.clone().close();
+    }
+}
+
+/// Tray hosts that may show the icon.
+struct Hosts {
+    watcher: bool,
+    xembed: bool,
+}
+
+/// Emits [`TrayIconEvent::HostAvailabilityChanged`] once neither host is running
+/// anymore and once one of them is again.
+struct HostAvailability {
+    id: TrayIconId,
+    hosts: Mutex<Hosts>,
+}
+
+impl HostAvailability {
+    fn update(&self, f: impl FnOnce(&mut Hosts)) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let was_available = hosts.watcher || hosts.xembed;
+        f(&mut hosts);
+
+        let available = hosts.watcher || hosts.xembed;
+        if available != was_available {
+            TrayIconEvent::send(TrayIconEvent::HostAvailabilityChanged {
+                id: self.id.clone(),
+                available,
+                timestamp: EventTimestamp::now(),
+            });
+        }
+    }
+
+    fn set_watcher(&self, running: bool) {
+        self.update(|hosts| hosts.watcher = running);
+    }
+
+    #[cfg(feature = "xembed")]
+    fn set_xembed(&self, running: bool) {
+        self.update(|hosts| hosts.xembed = running);
+    }
+}
+
+pub fn is_tray_available() -> bool {
+    let available = Connection::session().is_ok_and(|connection| watcher_running(&connection));
+    #[cfg(feature = "xembed")]
+    let available = available || xembed::is_tray_available(None).unwrap_or(false);
+    available
+}
+
+fn watcher_running(connection: &Connection) -> bool {
+    DBusProxy::new(connection)
+        .and_then(|dbus| Ok(dbus.name_has_owner(BusName::from_static_str(WATCHER_NAME)?)?))
+        .unwrap_or(false)
+}
+
+/// Icons are sent over D-Bus, no file is ever written.
+pub fn cleanup_temp_icons(_temp_icon_dir: Option<&Path>) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use zbus::{blocking::connection, interface};
+
+    use super::{
+        item::{ITEM_PATH, NO_MENU_PATH},
+        watcher_running, TrayIcon, TrayStatus,
+    };
+    use crate::{
+        dpi::{PhysicalPosition, PhysicalSize},
+        tests::EVENTS,
+        MouseButton, MouseButtonState, Rect, ScrollOrientation, TrayIconAttributes, TrayIconEvent,
+        TrayIconId,
+    };
+
+    /// A private session bus, killed on drop.
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        /// Panics if `dbus-daemon` is not available, the tests using it are ignored by default.
+        fn spawn() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("failed to run dbus-daemon");
+
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.as_mut().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+
+            Self {
+                daemon,
+                address: address.trim().to_string(),
+            }
+        }
+
+        fn builder(&self) -> connection::Builder<'_> {
+            connection::Builder::address(self.address.as_str()).unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+        }
+    }
+
+    #[derive(Default, Clone)]
+    struct StubWatcher(Arc<Mutex<Vec<String>>>);
+
+    #[interface(name = "org.kde.StatusNotifierWatcher")]
+    impl StubWatcher {
+        fn register_status_notifier_item(&self, service: &str) {
+            self.0.lock().unwrap().push(service.to_string());
+        }
+    }
+
+    fn stub_watcher(bus: &Bus) -> (zbus::blocking::Connection, StubWatcher) {
+        let watcher = StubWatcher::default();
+        let connection = bus
+            .builder()
+            .name("org.kde.StatusNotifierWatcher")
+            .unwrap()
+            .serve_at("/StatusNotifierWatcher", watcher.clone())
+            .unwrap()
+            .build()
+            .unwrap();
+        (connection, watcher)
+    }
+
+    fn wait_for(mut condition: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn item_proxy(
+        bus: &Bus,
+        tray: &TrayIcon,
+    ) -> (zbus::blocking::Connection, zbus::blocking::Proxy<'static>) {
+        let connection = bus.builder().build().unwrap();
+        let proxy = zbus::blocking::proxy::Builder::new(&connection)
+            .destination(tray.name.clone())
+            .unwrap()
+            .path(ITEM_PATH)
+            .unwrap()
+            .interface("org.kde.StatusNotifierItem")
+            .unwrap()
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .unwrap();
+        (connection, proxy)
+    }
+
+    fn pixmaps(proxy: &zbus::blocking::Proxy<'_>, name: &str) -> Vec<(i32, i32, Vec<u8>)> {
+        proxy.get_property(name).unwrap()
+    }
+
+    /// A tray icon registered with a stub watcher on a private bus.
+    struct RegisteredTray {
+        tray: TrayIcon,
+        watcher: StubWatcher,
+        _watcher_connection: zbus::blocking::Connection,
+        bus: Bus,
+    }
+
+    impl RegisteredTray {
+        fn new(id: &str, attrs: TrayIconAttributes) -> Self {
+            let bus = Bus::spawn();
+            let (watcher_connection, watcher) = stub_watcher(&bus);
+            let tray = TrayIcon::with_builder(bus.builder(), TrayIconId::new(id), attrs).unwrap();
+            Self {
+                tray,
+                watcher,
+                _watcher_connection: watcher_connection,
+                bus,
+            }
+        }
+
+        fn proxy(&self) -> (zbus::blocking::Connection, zbus::blocking::Proxy<'static>) {
+            item_proxy(&self.bus, &self.tray)
+        }
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn registers_and_exports_item() {
+        let registered = RegisteredTray::new(
+            "id",
+            TrayIconAttributes {
+                title: Some("title".to_string()),
+                category: crate::TrayCategory::Hardware,
+                icon: Some(crate::Icon::from_rgba(vec![1, 2, 3, 4], 1, 1).unwrap()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            *registered.watcher.0.lock().unwrap(),
+            vec![registered.tray.name.clone()]
+        );
+
+        let (_connection, proxy) = registered.proxy();
+        assert_eq!(proxy.get_property::<String>("Title").unwrap(), "title");
+        assert_eq!(
+            proxy.get_property::<String>("Category").unwrap(),
+            "Hardware"
+        );
+        let exe = std::env::current_exe().unwrap();
+        assert_eq!(
+            proxy.get_property::<String>("Id").unwrap(),
+            format!("{}-id", exe.file_stem().unwrap().to_str().unwrap())
+        );
+        assert_eq!(proxy.get_property::<String>("Status").unwrap(), "Active");
+        assert_eq!(
+            pixmaps(&proxy, "IconPixmap"),
+            vec![(1, 1, vec![4, 1, 2, 3])]
+        );
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn exports_tooltip() {
+        let mut registered = RegisteredTray::new(
+            "tooltip",
+            TrayIconAttributes {
+                tooltip: Some("tooltip".into()),
+                ..Default::default()
+            },
+        );
+
+        let (_connection, proxy) = registered.proxy();
+        let tooltip = || {
+            proxy
+                .get_property::<(String, Vec<(i32, i32, Vec<u8>)>, String, String)>("ToolTip")
+                .unwrap()
+        };
+        assert_eq!(tooltip().2, "tooltip");
+
+        registered
+            .tray
+            .set_tooltip(Some(
+                crate::Tooltip::new("changed")
+                    .with_body("<b>body</b>")
+                    .with_icon(crate::Icon::from_rgba(vec![1, 2, 3, 4], 1, 1).unwrap()),
+            ))
+            .unwrap();
+        assert_eq!(
+            tooltip(),
+            (
+                String::new(),
+                vec![(1, 1, vec![4, 1, 2, 3])],
+                "changed".to_string(),
+                "<b>body</b>".to_string()
+            )
+        );
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn exports_overlay_icon() {
+        let mut registered = RegisteredTray::new("overlay", TrayIconAttributes::default());
+
+        let (_connection, proxy) = registered.proxy();
+        assert_eq!(pixmaps(&proxy, "OverlayIconPixmap"), vec![]);
+
+        registered
+            .tray
+            .set_overlay_icon(Some(
+                crate::Icon::from_rgba(vec![9, 10, 11, 12], 1, 1).unwrap(),
+            ))
+            .unwrap();
+        assert_eq!(
+            pixmaps(&proxy, "OverlayIconPixmap"),
+            vec![(1, 1, vec![12, 9, 10, 11])]
+        );
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn composites_overlay_into_icon_pixmap() {
+        let base = |rgb: [u8; 3]| {
+            let pixel = [rgb[0], rgb[1], rgb[2], 255];
+            crate::Icon::from_rgba(pixel.repeat(4), 2, 2).unwrap()
+        };
+        let mut registered = RegisteredTray::new(
+            "composite",
+            TrayIconAttributes {
+                icon: Some(base([1, 2, 3])),
+                composite_overlay: true,
+                ..Default::default()
+            },
+        );
+
+        let (_connection, proxy) = registered.proxy();
+        // the overlay covers the bottom right pixel
+        let composited = |rgb: [u8; 3]| {
+            let pixel = [255, rgb[0], rgb[1], rgb[2]];
+            let mut argb = pixel.repeat(3);
+            argb.extend([255, 9, 10, 11]);
+            vec![(2, 2, argb)]
+        };
+
+        let tray = &mut registered.tray;
+        tray.set_overlay_icon(Some(
+            crate::Icon::from_rgba(vec![9, 10, 11, 255], 1, 1).unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(pixmaps(&proxy, "IconPixmap"), composited([1, 2, 3]));
+        assert_eq!(pixmaps(&proxy, "OverlayIconPixmap"), vec![]);
+
+        // the overlay is kept when the icon changes
+        tray.set_icon(Some(base([4, 5, 6]))).unwrap();
+        assert_eq!(pixmaps(&proxy, "IconPixmap"), composited([4, 5, 6]));
+
+        tray.set_overlay_icon(None).unwrap();
+        assert_eq!(
+            pixmaps(&proxy, "IconPixmap"),
+            vec![(2, 2, [255, 4, 5, 6].repeat(4))]
+        );
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn exports_no_menu_path_without_menu() {
+        let mut registered = RegisteredTray::new("menu", TrayIconAttributes::default());
+
+        let (_connection, proxy) = registered.proxy();
+        let menu_path = || {
+            proxy
+                .get_property::<zbus::zvariant::OwnedObjectPath>("Menu")
+                .unwrap()
+        };
+        assert_eq!(menu_path().as_str(), NO_MENU_PATH);
+        registered.tray.set_menu(None);
+        assert_eq!(menu_path().as_str(), NO_MENU_PATH);
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn exports_status_and_attention() {
+        let mut registered = RegisteredTray::new("status", TrayIconAttributes::default());
+
+        let (_connection, proxy) = registered.proxy();
+        let status = || proxy.get_property::<String>("Status").unwrap();
+
+        let tray = &mut registered.tray;
+        tray.set_visible(false).unwrap();
+        assert_eq!(status(), "Passive");
+
+        tray.set_attention_icon(Some(
+            crate::Icon::from_rgba(vec![5, 6, 7, 8], 1, 1).unwrap(),
+        ))
+        .unwrap();
+        tray.set_attention_movie_name(Some("blink")).unwrap();
+        tray.set_status(TrayStatus::NeedsAttention).unwrap();
+        assert_eq!(status(), "NeedsAttention");
+        assert_eq!(
+            pixmaps(&proxy, "AttentionIconPixmap"),
+            vec![(1, 1, vec![8, 5, 6, 7])]
+        );
+        assert_eq!(
+            proxy.get_property::<String>("AttentionMovieName").unwrap(),
+            "blink"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn registers_again_when_watcher_restarts() {
+        let bus = Bus::spawn();
+
+        // created before any watcher is running
+        let tray = TrayIcon::with_builder(
+            bus.builder(),
+            TrayIconId::new("restart"),
+            TrayIconAttributes::default(),
+        )
+        .unwrap();
+
+        for _ in 0..2 {
+            let (watcher_connection, watcher) = stub_watcher(&bus);
+            wait_for(|| *watcher.0.lock().unwrap() == [tray.name.clone()]);
+            watcher_connection
+                .release_name("org.kde.StatusNotifierWatcher")
+                .unwrap();
+        }
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn reports_host_availability() {
+        let bus = Bus::spawn();
+
+        let _events = EVENTS.lock().unwrap();
+        let id = TrayIconId::new("sni-host");
+        let _tray =
+            TrayIcon::with_builder(bus.builder(), id.clone(), TrayIconAttributes::default())
+                .unwrap();
+
+        let next_availability = || loop {
+            match TrayIconEvent::receiver()
+                .recv_timeout(Duration::from_secs(5))
+                .unwrap()
+            {
+                TrayIconEvent::HostAvailabilityChanged {
+                    id: event_id,
+                    available,
+                    ..
+                } if event_id == id => break available,
+                _ => {}
+            }
+        };
+
+        let connection = bus.builder().build().unwrap();
+        assert!(!watcher_running(&connection));
+
+        let (watcher_connection, _) = stub_watcher(&bus);
+        assert!(next_availability());
+        assert!(watcher_running(&connection));
+
+        watcher_connection
+            .release_name("org.kde.StatusNotifierWatcher")
+            .unwrap();
+        assert!(!next_availability());
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn emits_click_events() {
+        let _events = EVENTS.lock().unwrap();
+        let id = TrayIconId::new("sni-click");
+        let registered = RegisteredTray::new(id.as_ref(), TrayIconAttributes::default());
+        let tray = &registered.tray;
+
+        assert_eq!(tray.rect(), None);
+
+        let (_connection, proxy) = registered.proxy();
+        for method in ["Activate", "SecondaryActivate", "ContextMenu"] {
+            proxy.call_method(method, &(10, 20)).unwrap();
+        }
+
+        let activated = Rect {
+            size: PhysicalSize::new(0, 0),
+            position: PhysicalPosition::new(10., 20.),
+        };
+        assert_eq!(tray.rect(), Some(activated));
+
+        let mut sequence = 0;
+        let clicks: Vec<_> = TrayIconEvent::receiver()
+            .try_iter()
+            .filter(|event| event.id() == &id)
+            .inspect(|event| {
+                assert!(event.timestamp().sequence > sequence);
+                assert_eq!(event.timestamp().platform_time, None);
+                sequence = event.timestamp().sequence;
+            })
+            .map(|event| match event {
+                TrayIconEvent::Click {
+                    position,
+                    rect,
+                    button,
+                    button_state,
+                    modifiers,
+                    ..
+                } => {
+                    assert_eq!(rect, activated);
+                    assert!(modifiers.is_empty());
+                    (position.x, position.y, button, button_state)
+                }
+                _ => panic!("unexpected event {event:?}"),
+            })
+            .collect();
+
+        assert_eq!(
+            clicks,
+            [MouseButton::Left, MouseButton::Middle, MouseButton::Right]
+                .into_iter()
+                .flat_map(|button| {
+                    [MouseButtonState::Down, MouseButtonState::Up]
+                        .map(|state| (10.0, 20.0, button, state))
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn emits_scroll_events() {
+        let _events = EVENTS.lock().unwrap();
+        let id = TrayIconId::new("sni-scroll");
+        let registered = RegisteredTray::new(id.as_ref(), TrayIconAttributes::default());
+
+        let (_connection, proxy) = registered.proxy();
+        proxy.call_method("Scroll", &(-120, "vertical")).unwrap();
+        proxy.call_method("Scroll", &(1, "Horizontal")).unwrap();
+        assert!(proxy.call_method("Scroll", &(1, "diagonal")).is_err());
+
+        let scrolls: Vec<_> = TrayIconEvent::receiver()
+            .try_iter()
+            .filter(|event| event.id() == &id)
+            .map(|event| match event {
+                TrayIconEvent::Scroll {
+                    delta, orientation, ..
+                } => (delta, orientation),
+                _ => panic!("unexpected event {event:?}"),
+            })
+            .collect();
+
+        assert_eq!(
+            scrolls,
+            [
+                (-120, ScrollOrientation::Vertical),
+                (1, ScrollOrientation::Horizontal)
+            ]
+        );
+    }
+}
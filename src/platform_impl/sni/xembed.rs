@@ -0,0 +1,1139 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Fallback for X11 desktops without a StatusNotifierWatcher, the icon is docked
+//! in the tray with the [System Tray Protocol](https://specifications.freedesktop.org/systemtray-spec/)
+//! as an XEmbed client window that we draw and receive input events for.
+//!
+//! The tray is kept connected while a watcher is running and only docks the icon
+//! when it goes away, see [`XEmbedTray::set_enabled`].
+//!
+//! The window is handled by a dedicated thread with its own X connection,
+//! changes are made to the shared [`State`] and the thread is woken up with a
+//! client message sent to an unmapped control window.
+
+use std::{
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use gtk::{glib, prelude::*};
+use x11rb::{
+    connection::Connection,
+    protocol::{xproto::*, Event},
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+use super::icon::downscale;
+use crate::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    icon::{Icon, RgbaIcon, PIXEL_SIZE},
+    EventTimestamp, Modifiers, MouseButton, MouseButtonState, Rect, ScrollOrientation,
+    TrayIconEvent, TrayIconId, TrayStatus,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+/// `opcode` of the `_NET_SYSTEM_TRAY_OPCODE` message asking the tray to embed a window.
+const SYSTEM_TRAY_REQUEST_DOCK: u32 = 0;
+/// Flag of `_XEMBED_INFO` asking the embedder to map the window.
+const XEMBED_MAPPED: u32 = 1;
+/// Size of the window until the tray resizes it.
+const DEFAULT_SIZE: u16 = 24;
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        MANAGER,
+        _NET_SYSTEM_TRAY_OPCODE,
+        _NET_SYSTEM_TRAY_VISUAL,
+        _XEMBED_INFO,
+        _TRAY_ICON_UPDATE,
+    }
+}
+
+#[derive(Default)]
+struct State {
+    icon: Option<Icon>,
+    overlay_icon: Option<Icon>,
+    attention_icon: Option<Icon>,
+    status: TrayStatus,
+    menu: Option<glib::SendWeakRef<gtk::Menu>>,
+    /// Whether the icon should be docked, i.e. no StatusNotifierWatcher is running.
+    enabled: bool,
+    quit: bool,
+}
+
+impl State {
+    fn docked(&self) -> bool {
+        self.enabled && self.status != TrayStatus::Passive
+    }
+}
+
+struct Shared {
+    id: TrayIconId,
+    conn: RustConnection,
+    atoms: Atoms,
+    screen: usize,
+    selection: Atom,
+    control: Window,
+    state: Mutex<State>,
+    /// Whether a tray is running, with or without the icon docked.
+    has_tray: AtomicBool,
+    /// The docked window, `NONE` if the icon is not docked.
+    window: AtomicU32,
+    on_tray_change: Box<dyn Fn(bool) + Send + Sync>,
+}
+
+pub struct XEmbedTray {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl XEmbedTray {
+    /// Connects to `display`, or `$DISPLAY` if `None`, and docks the icon as soon as
+    /// a tray is available if `enabled`.
+    ///
+    /// `on_tray_change` is called from another thread when a tray starts or goes away.
+    pub fn new(
+        display: Option<&str>,
+        id: TrayIconId,
+        icon: Option<Icon>,
+        menu: Option<&gtk::Menu>,
+        enabled: bool,
+        on_tray_change: impl Fn(bool) + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let (conn, screen) = RustConnection::connect(display)?;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        let selection = tray_selection(&conn, screen)?;
+
+        let root = conn.setup().roots[screen].root;
+        // a new tray announces itself with a `MANAGER` message sent to the root window
+        conn.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+
+        let control = conn.generate_id()?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            control,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )?;
+
+        let manager = conn.get_selection_owner(selection)?.reply()?.owner;
+        watch_manager(&conn, manager)?;
+        conn.flush()?;
+
+        let shared = Arc::new(Shared {
+            id,
+            conn,
+            atoms,
+            screen,
+            selection,
+            control,
+            state: Mutex::new(State {
+                icon,
+                menu: menu.map(|menu| menu.downgrade().into()),
+                enabled,
+                ..Default::default()
+            }),
+            has_tray: AtomicBool::new(manager != NONE),
+            window: AtomicU32::new(NONE),
+            on_tray_change: Box::new(on_tray_change),
+        });
+
+        let thread = std::thread::Builder::new()
+            .name("tray-icon-xembed".into())
+            .spawn({
+                let shared = shared.clone();
+                move || {
+                    let _ = Embedder {
+                        shared,
+                        manager,
+                        window: None,
+                    }
+                    .run();
+                }
+            })?;
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        f(&mut self.shared.state.lock().unwrap());
+        let _ = self.shared.wake();
+    }
+
+    /// Whether a tray is running, the icon may still not be docked while disabled.
+    pub fn has_tray(&self) -> bool {
+        self.shared.has_tray.load(Ordering::Relaxed)
+    }
+
+    /// Geometry of the docked window in root coordinates.
+    pub fn rect(&self) -> Option<Rect> {
+        let window = self.shared.window.load(Ordering::Relaxed);
+        if window == NONE {
+            return None;
+        }
+
+        // fails if the window is destroyed in the meantime
+        let conn = &self.shared.conn;
+        let geometry = conn.get_geometry(window).ok()?.reply().ok()?;
+        let origin = conn
+            .translate_coordinates(window, self.shared.root(), 0, 0)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        Some(Rect {
+            size: PhysicalSize::new(geometry.width as u32, geometry.height as u32),
+            position: PhysicalPosition::new(origin.dst_x as f64, origin.dst_y as f64),
+        })
+    }
+
+    pub fn set_icon(&self, icon: Option<Icon>) {
+        self.update(|state| state.icon = icon);
+    }
+
+    pub fn set_overlay_icon(&self, icon: Option<Icon>) {
+        self.update(|state| state.overlay_icon = icon);
+    }
+
+    pub fn set_attention_icon(&self, icon: Option<Icon>) {
+        self.update(|state| state.attention_icon = icon);
+    }
+
+    pub fn set_status(&self, status: TrayStatus) {
+        self.update(|state| state.status = status);
+    }
+
+    pub fn set_menu(&self, menu: Option<&gtk::Menu>) {
+        self.update(|state| state.menu = menu.map(|menu| menu.downgrade().into()));
+    }
+
+    /// Docks or undocks the icon, used while no StatusNotifierWatcher is running.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.update(|state| state.enabled = enabled);
+    }
+}
+
+impl Drop for XEmbedTray {
+    fn drop(&mut self) {
+        self.update(|state| state.quit = true);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Whether a tray is running on `display`, or `$DISPLAY` if `None`.
+pub fn is_tray_available(display: Option<&str>) -> Result<bool> {
+    let (conn, screen) = RustConnection::connect(display)?;
+    let selection = tray_selection(&conn, screen)?;
+    let owner = conn.get_selection_owner(selection)?.reply()?.owner;
+    Ok(owner != NONE)
+}
+
+fn log_error(error: &(dyn Error + Send + Sync)) {
+    eprintln!("Error in the X11 system tray: {error}");
+}
+
+/// `_NET_SYSTEM_TRAY_S{screen}`, the selection owned by the tray.
+fn tray_selection(conn: &RustConnection, screen: usize) -> Result<Atom> {
+    Ok(conn
+        .intern_atom(false, format!("_NET_SYSTEM_TRAY_S{screen}").as_bytes())?
+        .reply()?
+        .atom)
+}
+
+/// Selects the `DestroyNotify` of the tray window to know when it goes away.
+fn watch_manager(conn: &RustConnection, manager: Window) -> Result<()> {
+    if manager != NONE {
+        conn.change_window_attributes(
+            manager,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+    }
+    Ok(())
+}
+
+impl Shared {
+    fn root(&self) -> Window {
+        self.conn.setup().roots[self.screen].root
+    }
+
+    /// Makes the thread apply the changes made to the state.
+    fn wake(&self) -> Result<()> {
+        let event =
+            ClientMessageEvent::new(32, self.control, self.atoms._TRAY_ICON_UPDATE, [0u32; 5]);
+        // without an event mask, the event goes to the client that created the window
+        self.conn
+            .send_event(false, self.control, EventMask::NO_EVENT, event)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+/// The window embedded in the tray.
+struct IconWindow {
+    window: Window,
+    gc: Gcontext,
+    colormap: Option<Colormap>,
+    depth: u8,
+    width: u16,
+    height: u16,
+    /// Whether the tray mapped the window, it can't be drawn before.
+    mapped: bool,
+}
+
+impl IconWindow {
+    fn argb(&self) -> bool {
+        self.colormap.is_some()
+    }
+
+    /// Position and size of the window, in root coordinates, from an event
+    /// reporting the pointer in both the window and the root coordinates.
+    fn rect(&self, event_x: i16, event_y: i16, root_x: i16, root_y: i16) -> Rect {
+        Rect {
+            size: PhysicalSize::new(self.width as u32, self.height as u32),
+            position: PhysicalPosition::new((root_x - event_x) as f64, (root_y - event_y) as f64),
+        }
+    }
+}
+
+struct Embedder {
+    shared: Arc<Shared>,
+    /// Window of the running tray, `NONE` if there is none.
+    manager: Window,
+    window: Option<IconWindow>,
+}
+
+impl Embedder {
+    fn run(&mut self) -> Result<()> {
+        if let Err(e) = self.dock() {
+            log_error(&*e);
+        }
+
+        loop {
+            // only fails once the connection is broken
+            let event = self.shared.conn.wait_for_event()?;
+            match self.handle(event) {
+                Ok(true) => break,
+                Ok(false) => {}
+                // The requests waiting for a reply fail, e.g. with `BadWindow` when the
+                // tray goes away while docking. The icon is docked again with the next
+                // tray, skip the event so the thread keeps running until then.
+                Err(e) => log_error(&*e),
+            }
+        }
+
+        self.undock()?;
+        self.shared.conn.destroy_window(self.shared.control)?;
+        // the window is gone once the tray icon is dropped
+        self.shared.conn.sync()?;
+        Ok(())
+    }
+
+    /// Handles an event of the connection, returns `true` once the tray icon is dropped.
+    fn handle(&mut self, event: Event) -> Result<bool> {
+        match event {
+            Event::ClientMessage(e) if e.type_ == self.shared.atoms._TRAY_ICON_UPDATE => {
+                let (quit, docked) = {
+                    let state = self.shared.state.lock().unwrap();
+                    (state.quit, state.docked())
+                };
+                if quit {
+                    return Ok(true);
+                }
+
+                match (&self.window, docked) {
+                    // drawn on the first `Expose` once the tray maps it otherwise
+                    (Some(window), true) if window.mapped => self.draw(window)?,
+                    (Some(_), true) | (None, false) => {}
+                    (None, true) | (Some(_), false) => self.dock()?,
+                }
+            }
+            Event::ClientMessage(e)
+                if e.type_ == self.shared.atoms.MANAGER
+                    && e.data.as_data32()[1] == self.shared.selection =>
+            {
+                let manager = e.data.as_data32()[2];
+                if manager != self.manager {
+                    self.set_manager(manager)?;
+                    self.dock()?;
+                }
+            }
+            Event::DestroyNotify(e) if e.window == self.manager => {
+                self.set_manager(NONE)?;
+            }
+            // the tray went away and gave the window back to the root window
+            Event::ReparentNotify(e) if e.parent == self.shared.root() => {
+                self.undock()?;
+            }
+            Event::ConfigureNotify(e) => {
+                if let Some(window) = self.window.as_mut().filter(|w| w.window == e.window) {
+                    if (window.width, window.height) != (e.width, e.height) {
+                        window.width = e.width;
+                        window.height = e.height;
+                        self.shared.conn.clear_area(true, e.window, 0, 0, 0, 0)?;
+                        self.shared.conn.flush()?;
+                    }
+                }
+            }
+            Event::MapNotify(e) => {
+                if let Some(window) = self.window.as_mut().filter(|w| w.window == e.window) {
+                    window.mapped = true;
+                }
+            }
+            Event::UnmapNotify(e) => {
+                if let Some(window) = self.window.as_mut().filter(|w| w.window == e.window) {
+                    window.mapped = false;
+                }
+            }
+            Event::Expose(e) if e.count == 0 => {
+                let window = self.window.as_ref();
+                if let Some(window) = window.filter(|w| w.window == e.window && w.mapped) {
+                    self.draw(window)?;
+                }
+            }
+            Event::ButtonPress(e) => self.button(&e, MouseButtonState::Down),
+            Event::ButtonRelease(e) => self.button(&e, MouseButtonState::Up),
+            Event::MotionNotify(e) => {
+                if let Some(window) = &self.window {
+                    TrayIconEvent::send(TrayIconEvent::Move {
+                        id: self.shared.id.clone(),
+                        position: PhysicalPosition::new(e.root_x as f64, e.root_y as f64),
+                        rect: window.rect(e.event_x, e.event_y, e.root_x, e.root_y),
+                        timestamp: EventTimestamp::now().with_platform_time(e.time.into()),
+                    });
+                }
+            }
+            Event::EnterNotify(e) => {
+                if let Some(window) = &self.window {
+                    TrayIconEvent::send(TrayIconEvent::Enter {
+                        id: self.shared.id.clone(),
+                        position: PhysicalPosition::new(e.root_x as f64, e.root_y as f64),
+                        rect: window.rect(e.event_x, e.event_y, e.root_x, e.root_y),
+                        timestamp: EventTimestamp::now().with_platform_time(e.time.into()),
+                    });
+                }
+            }
+            Event::LeaveNotify(e) => {
+                if let Some(window) = &self.window {
+                    TrayIconEvent::send(TrayIconEvent::Leave {
+                        id: self.shared.id.clone(),
+                        position: PhysicalPosition::new(e.root_x as f64, e.root_y as f64),
+                        rect: window.rect(e.event_x, e.event_y, e.root_x, e.root_y),
+                        timestamp: EventTimestamp::now().with_platform_time(e.time.into()),
+                    });
+                }
+            }
+            // errors of the requests without reply, e.g. `BadWindow` when docking
+            // in a tray that just went away, arrive as events and are ignored
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    /// Creates the window and asks the tray to embed it, a tray that isn't running
+    /// yet is waited for.
+    fn dock(&mut self) -> Result<()> {
+        self.undock()?;
+
+        if !self.shared.state.lock().unwrap().docked() {
+            return Ok(());
+        }
+
+        let manager = self.manager;
+        if manager == NONE {
+            return Ok(());
+        }
+
+        let conn = &self.shared.conn;
+
+        let screen = &conn.setup().roots[self.shared.screen];
+        let event_mask = EventMask::EXPOSURE
+            | EventMask::STRUCTURE_NOTIFY
+            | EventMask::BUTTON_PRESS
+            | EventMask::BUTTON_RELEASE
+            | EventMask::ENTER_WINDOW
+            | EventMask::LEAVE_WINDOW
+            | EventMask::POINTER_MOTION;
+
+        let window = conn.generate_id()?;
+        let (depth, colormap) = match self.argb_visual(manager)? {
+            // transparent window with the visual the tray asked for
+            Some(visual) => {
+                let colormap = conn.generate_id()?;
+                conn.create_colormap(ColormapAlloc::NONE, colormap, screen.root, visual)?;
+                conn.create_window(
+                    32,
+                    window,
+                    screen.root,
+                    0,
+                    0,
+                    DEFAULT_SIZE,
+                    DEFAULT_SIZE,
+                    0,
+                    WindowClass::INPUT_OUTPUT,
+                    visual,
+                    &CreateWindowAux::new()
+                        .background_pixel(0)
+                        .border_pixel(0)
+                        .colormap(colormap)
+                        .event_mask(event_mask),
+                )?;
+                (32, Some(colormap))
+            }
+            // the tray background is drawn below the icon instead
+            None => {
+                conn.create_window(
+                    screen.root_depth,
+                    window,
+                    screen.root,
+                    0,
+                    0,
+                    DEFAULT_SIZE,
+                    DEFAULT_SIZE,
+                    0,
+                    WindowClass::INPUT_OUTPUT,
+                    screen.root_visual,
+                    &CreateWindowAux::new()
+                        .background_pixmap(Pixmap::from(BackPixmap::PARENT_RELATIVE))
+                        .event_mask(event_mask),
+                )?;
+                (screen.root_depth, None)
+            }
+        };
+
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, window, &CreateGCAux::new())?;
+
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.shared.atoms._XEMBED_INFO,
+            self.shared.atoms._XEMBED_INFO,
+            &[0, XEMBED_MAPPED],
+        )?;
+
+        conn.send_event(
+            false,
+            manager,
+            EventMask::NO_EVENT,
+            ClientMessageEvent::new(
+                32,
+                manager,
+                self.shared.atoms._NET_SYSTEM_TRAY_OPCODE,
+                [CURRENT_TIME, SYSTEM_TRAY_REQUEST_DOCK, window, 0, 0],
+            ),
+        )?;
+        conn.flush()?;
+
+        self.shared.window.store(window, Ordering::Relaxed);
+        self.window = Some(IconWindow {
+            window,
+            gc,
+            colormap,
+            depth,
+            width: DEFAULT_SIZE,
+            height: DEFAULT_SIZE,
+            mapped: false,
+        });
+
+        Ok(())
+    }
+
+    fn set_manager(&mut self, manager: Window) -> Result<()> {
+        watch_manager(&self.shared.conn, manager)?;
+        self.manager = manager;
+
+        let has_tray = manager != NONE;
+        if self.shared.has_tray.swap(has_tray, Ordering::Relaxed) != has_tray {
+            (self.shared.on_tray_change)(has_tray);
+        }
+        Ok(())
+    }
+
+    fn undock(&mut self) -> Result<()> {
+        if let Some(window) = self.window.take() {
+            self.shared.window.store(NONE, Ordering::Relaxed);
+            let conn = &self.shared.conn;
+            conn.free_gc(window.gc)?;
+            conn.destroy_window(window.window)?;
+            if let Some(colormap) = window.colormap {
+                conn.free_colormap(colormap)?;
+            }
+            conn.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The 32 bit visual advertised by the tray in `_NET_SYSTEM_TRAY_VISUAL`, if any.
+    fn argb_visual(&self, manager: Window) -> Result<Option<Visualid>> {
+        let conn = &self.shared.conn;
+        let visual = conn
+            .get_property(
+                false,
+                manager,
+                self.shared.atoms._NET_SYSTEM_TRAY_VISUAL,
+                AtomEnum::VISUALID,
+                0,
+                1,
+            )?
+            .reply()?
+            .value32()
+            .and_then(|mut value| value.next());
+
+        let screen = &conn.setup().roots[self.shared.screen];
+        Ok(visual.filter(|&visual| {
+            screen
+                .allowed_depths
+                .iter()
+                .filter(|depth| depth.depth == 32)
+                .flat_map(|depth| &depth.visuals)
+                .any(|v| v.visual_id == visual && v.class == VisualClass::TRUE_COLOR)
+        }))
+    }
+
+    fn draw(&self, window: &IconWindow) -> Result<()> {
+        let icon = {
+            let state = self.shared.state.lock().unwrap();
+            match (&state.attention_icon, state.status) {
+                (Some(icon), TrayStatus::NeedsAttention) => Some(icon.clone()),
+                _ => Icon::with_overlay(state.icon.as_ref(), state.overlay_icon.as_ref()),
+            }
+        };
+
+        let conn = &self.shared.conn;
+        let (width, height) = (window.width, window.height);
+        let len = width as usize * height as usize * PIXEL_SIZE;
+
+        let mut pixels = if window.argb() {
+            vec![0; len]
+        } else {
+            conn.clear_area(false, window.window, 0, 0, 0, 0)?;
+            conn.get_image(
+                ImageFormat::Z_PIXMAP,
+                window.window,
+                0,
+                0,
+                width,
+                height,
+                !0,
+            )?
+            .reply()?
+            .data
+        };
+
+        // only 32 bits per pixel is supported, the tray background is left as is otherwise
+        if pixels.len() != len {
+            return Ok(());
+        }
+
+        if let Some(icon) = icon.and_then(|icon| icon.inner.to_rgba()) {
+            let lsb_first = conn.setup().image_byte_order == ImageOrder::LSB_FIRST;
+            composite(
+                &mut pixels,
+                width as u32,
+                height as u32,
+                &icon,
+                window.argb(),
+                lsb_first,
+            );
+        }
+
+        conn.put_image(
+            ImageFormat::Z_PIXMAP,
+            window.window,
+            window.gc,
+            width,
+            height,
+            0,
+            0,
+            0,
+            window.depth,
+            &pixels,
+        )?;
+        conn.flush()?;
+
+        Ok(())
+    }
+
+    fn button(&self, event: &ButtonPressEvent, button_state: MouseButtonState) {
+        let Some(window) = &self.window else {
+            return;
+        };
+
+        let id = self.shared.id.clone();
+        let position = PhysicalPosition::new(event.root_x as f64, event.root_y as f64);
+        let rect = window.rect(event.event_x, event.event_y, event.root_x, event.root_y);
+        let timestamp = EventTimestamp::now().with_platform_time(event.time.into());
+
+        let button = match event.detail {
+            1 => MouseButton::Left,
+            2 => MouseButton::Middle,
+            3 => MouseButton::Right,
+            // wheel buttons are only pressed, up, down, left and right
+            4..=7 => {
+                if button_state == MouseButtonState::Down {
+                    let (delta, orientation) = match event.detail {
+                        4 => (1, ScrollOrientation::Vertical),
+                        5 => (-1, ScrollOrientation::Vertical),
+                        6 => (-1, ScrollOrientation::Horizontal),
+                        _ => (1, ScrollOrientation::Horizontal),
+                    };
+                    TrayIconEvent::send(TrayIconEvent::Scroll {
+                        id,
+                        delta,
+                        orientation,
+                        position,
+                        rect,
+                        timestamp,
+                    });
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        TrayIconEvent::send(TrayIconEvent::Click {
+            id,
+            position,
+            rect,
+            button,
+            button_state,
+            modifiers: Modifiers {
+                shift: event.state.contains(KeyButMask::SHIFT),
+                control: event.state.contains(KeyButMask::CONTROL),
+                alt: event.state.contains(KeyButMask::MOD1),
+                super_key: event.state.contains(KeyButMask::MOD4),
+            },
+            timestamp,
+        });
+
+        // once the implicit grab of the press is gone so gtk can grab the pointer
+        if button == MouseButton::Right && button_state == MouseButtonState::Up {
+            if let Some(menu) = self.shared.state.lock().unwrap().menu.clone() {
+                let time = event.time;
+                glib::MainContext::default().invoke(move || {
+                    if let Some(menu) = menu.upgrade() {
+                        menu.popup_easy(3, time);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Draws `icon` centered over `pixels`, a 32 bits per pixel `width` x `height` image.
+///
+/// ARGB windows expect premultiplied alpha on a transparent background, otherwise
+/// the icon is blended with the tray background already in `pixels`.
+fn composite(
+    pixels: &mut [u8],
+    width: u32,
+    height: u32,
+    icon: &RgbaIcon,
+    argb: bool,
+    lsb_first: bool,
+) {
+    if icon.width == 0 || icon.height == 0 {
+        return;
+    }
+
+    // shrink the icon to fit, it is never scaled up to stay sharp
+    let largest = icon.width.max(icon.height);
+    let size = width.min(height);
+    let scaled;
+    let icon = if largest > size && size > 0 {
+        scaled = downscale(icon, size, largest);
+        &scaled
+    } else {
+        icon
+    };
+
+    // byte offsets of blue, green, red and alpha
+    let [b, g, r, a] = if lsb_first {
+        [0, 1, 2, 3]
+    } else {
+        [3, 2, 1, 0]
+    };
+
+    let x0 = width.saturating_sub(icon.width) / 2;
+    let y0 = height.saturating_sub(icon.height) / 2;
+    for y in 0..icon.height.min(height) {
+        for x in 0..icon.width.min(width) {
+            let src = ((y * icon.width + x) as usize) * PIXEL_SIZE;
+            let src = &icon.rgba[src..src + PIXEL_SIZE];
+            let dst = (((y0 + y) * width + x0 + x) as usize) * PIXEL_SIZE;
+            let dst = &mut pixels[dst..dst + PIXEL_SIZE];
+
+            let alpha = src[3] as u32;
+            let blend = |src: u8, dst: u8| {
+                ((src as u32 * alpha + dst as u32 * (255 - alpha) + 127) / 255) as u8
+            };
+            for (offset, color) in [(r, src[0]), (g, src[1]), (b, src[2])] {
+                dst[offset] = if argb {
+                    blend(color, 0)
+                } else {
+                    blend(color, dst[offset])
+                };
+            }
+            dst[a] = if argb { src[3] } else { 0 };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        time::{Duration, Instant},
+    };
+
+    use x11rb::protocol::xtest::ConnectionExt as _;
+
+    use super::*;
+    use crate::TrayIconEvent;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A virtual X server, killed on drop.
+    struct Xvfb {
+        server: Child,
+        display: String,
+    }
+
+    impl Xvfb {
+        /// Panics if `Xvfb` is not available, the tests using it are ignored by default.
+        fn spawn() -> Self {
+            let mut server = Command::new("Xvfb")
+                .args(["-displayfd", "1", "-nolisten", "tcp"])
+                .args(["-screen", "0", "640x480x24"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("failed to run Xvfb");
+
+            let mut display = String::new();
+            BufReader::new(server.stdout.as_mut().unwrap())
+                .read_line(&mut display)
+                .unwrap();
+
+            Self {
+                server,
+                display: format!(":{}", display.trim()),
+            }
+        }
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.server.kill();
+        }
+    }
+
+    /// The smallest tray manager embedding a single icon, its window is at
+    /// (100, 50) and the icon is embedded at (10, 20) in it.
+    struct TrayManager {
+        conn: RustConnection,
+        root: Window,
+        window: Window,
+        atoms: Atoms,
+    }
+
+    impl TrayManager {
+        fn start(display: &str) -> Self {
+            let (conn, screen) = RustConnection::connect(Some(display)).unwrap();
+            let atoms = Atoms::new(&conn).unwrap().reply().unwrap();
+            let selection = conn
+                .intern_atom(false, format!("_NET_SYSTEM_TRAY_S{screen}").as_bytes())
+                .unwrap()
+                .reply()
+                .unwrap()
+                .atom;
+
+            let screen = &conn.setup().roots[screen];
+            let (root, white) = (screen.root, screen.white_pixel);
+            let window = conn.generate_id().unwrap();
+            conn.create_window(
+                COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                100,
+                50,
+                100,
+                100,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                COPY_FROM_PARENT,
+                &CreateWindowAux::new()
+                    .background_pixel(white)
+                    .override_redirect(1),
+            )
+            .unwrap();
+            conn.map_window(window).unwrap();
+
+            conn.set_selection_owner(window, selection, CURRENT_TIME)
+                .unwrap();
+            conn.send_event(
+                false,
+                root,
+                EventMask::STRUCTURE_NOTIFY,
+                ClientMessageEvent::new(
+                    32,
+                    root,
+                    atoms.MANAGER,
+                    [CURRENT_TIME, selection, window, 0, 0],
+                ),
+            )
+            .unwrap();
+            conn.sync().unwrap();
+
+            Self {
+                conn,
+                root,
+                window,
+                atoms,
+            }
+        }
+
+        /// Waits for a dock request and returns the requested window.
+        fn dock_request(&self) -> Window {
+            let deadline = Instant::now() + TIMEOUT;
+            loop {
+                assert!(Instant::now() < deadline, "no dock request");
+                match self.conn.poll_for_event().unwrap() {
+                    Some(Event::ClientMessage(e))
+                        if e.type_ == self.atoms._NET_SYSTEM_TRAY_OPCODE
+                            && e.data.as_data32()[1] == SYSTEM_TRAY_REQUEST_DOCK =>
+                    {
+                        return e.data.as_data32()[2];
+                    }
+                    Some(_) => {}
+                    None => std::thread::sleep(Duration::from_millis(10)),
+                }
+            }
+        }
+
+        /// Embeds the window of a dock request.
+        fn embed(&self, icon: Window) {
+            let info: Vec<u32> = self
+                .conn
+                .get_property(
+                    false,
+                    icon,
+                    self.atoms._XEMBED_INFO,
+                    self.atoms._XEMBED_INFO,
+                    0,
+                    2,
+                )
+                .unwrap()
+                .reply()
+                .unwrap()
+                .value32()
+                .unwrap()
+                .collect();
+            assert_eq!(info, [0, XEMBED_MAPPED]);
+
+            self.conn
+                .reparent_window(icon, self.window, 10, 20)
+                .unwrap();
+            self.conn
+                .configure_window(icon, &ConfigureWindowAux::new().width(32).height(32))
+                .unwrap();
+            self.conn.map_window(icon).unwrap();
+            self.conn.sync().unwrap();
+        }
+
+        /// Pixel of `window` at (`x`, `y`) as RGB.
+        fn pixel(&self, window: Window, x: i16, y: i16) -> [u8; 3] {
+            let data = self
+                .conn
+                .get_image(ImageFormat::Z_PIXMAP, window, x, y, 1, 1, !0)
+                .unwrap()
+                .reply()
+                .unwrap()
+                .data;
+            [data[2], data[1], data[0]]
+        }
+
+        fn fake_input(&self, type_: u8, detail: u8, x: i16, y: i16) {
+            self.conn
+                .xtest_fake_input(type_, detail, CURRENT_TIME, self.root, x, y, 0)
+                .unwrap();
+            self.conn.sync().unwrap();
+        }
+    }
+
+    #[test]
+    fn composite_blends_and_premultiplies() {
+        let icon = RgbaIcon::from_rgba([255, 0, 0, 255, 0, 0, 255, 51].to_vec(), 2, 1).unwrap();
+
+        // centered on a gray background
+        let mut pixels = [128, 128, 128, 0].repeat(4 * 2);
+        composite(&mut pixels, 4, 2, &icon, false, true);
+        assert_eq!(&pixels[4..12], [0, 0, 255, 0, 153, 102, 102, 0]);
+        assert_eq!(&pixels[16..], [128, 128, 128, 0].repeat(4));
+
+        // scaled down to fit, most significant byte first
+        let mut pixels = vec![0; 4];
+        composite(&mut pixels, 1, 1, &icon, true, false);
+        assert_eq!(pixels, [153, 127, 0, 25]);
+
+        // empty icons leave the background as is
+        for (width, height) in [(0, 0), (0, 100), (100, 0)] {
+            let empty = RgbaIcon::from_rgba(Vec::new(), width, height).unwrap();
+            let mut pixels = [128, 128, 128, 0].repeat(4);
+            composite(&mut pixels, 2, 2, &empty, false, true);
+            assert_eq!(pixels, [128, 128, 128, 0].repeat(4));
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Xvfb"]
+    fn docks_draws_and_emits_events() {
+        let xvfb = Xvfb::spawn();
+
+        let _events = crate::tests::EVENTS.lock().unwrap();
+        let id = TrayIconId::new("xembed");
+        let icon = Icon::from_rgba([255, 0, 0, 255].repeat(16 * 16), 16, 16).unwrap();
+
+        // the tray starts after the icon, which waits for it
+        let (tray_changes, tray_change) = crossbeam_channel::unbounded();
+        let tray = XEmbedTray::new(
+            Some(&xvfb.display),
+            id.clone(),
+            Some(icon.clone()),
+            None,
+            true,
+            move |has_tray| {
+                let _ = tray_changes.send(has_tray);
+            },
+        )
+        .unwrap();
+        assert!(!tray.has_tray());
+        assert!(!is_tray_available(Some(&xvfb.display)).unwrap());
+
+        let manager = TrayManager::start(&xvfb.display);
+        let window = manager.dock_request();
+        // changed before the window is mapped, it can't be drawn yet
+        tray.set_icon(Some(
+            Icon::from_rgba([0, 0, 255, 255].repeat(16 * 16), 16, 16).unwrap(),
+        ));
+        tray.set_icon(Some(icon));
+        std::thread::sleep(Duration::from_millis(100));
+        manager.embed(window);
+        assert_eq!(tray_change.recv_timeout(TIMEOUT), Ok(true));
+        assert!(tray.has_tray());
+        assert!(is_tray_available(Some(&xvfb.display)).unwrap());
+
+        let rect = Rect {
+            size: PhysicalSize::new(32, 32),
+            position: PhysicalPosition::new(110., 70.),
+        };
+        assert_eq!(tray.rect(), Some(rect));
+
+        // the 16x16 icon is centered in the 32x32 window, over the tray background
+        let deadline = Instant::now() + TIMEOUT;
+        while manager.pixel(window, 16, 16) != [255, 0, 0] {
+            assert!(Instant::now() < deadline, "icon not drawn");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(manager.pixel(window, 2, 2), [255, 255, 255]);
+
+        manager.fake_input(MOTION_NOTIFY_EVENT, 0, 120, 80);
+        manager.fake_input(BUTTON_PRESS_EVENT, 1, 0, 0);
+        manager.fake_input(BUTTON_RELEASE_EVENT, 1, 0, 0);
+        manager.fake_input(BUTTON_PRESS_EVENT, 4, 0, 0);
+        manager.fake_input(BUTTON_RELEASE_EVENT, 4, 0, 0);
+        manager.fake_input(MOTION_NOTIFY_EVENT, 0, 5, 5);
+
+        let mut events = Vec::new();
+        while !matches!(events.last(), Some(TrayIconEvent::Leave { .. })) {
+            let event = TrayIconEvent::receiver().recv_timeout(TIMEOUT).unwrap();
+            if event.id() == &id {
+                events.push(event);
+            }
+        }
+
+        let inside = PhysicalPosition::new(120., 80.);
+        let summary: Vec<_> = events
+            .into_iter()
+            .filter_map(|event| match event {
+                TrayIconEvent::Enter {
+                    position, rect: r, ..
+                } => {
+                    assert_eq!((position, r), (inside, rect));
+                    Some("enter".to_string())
+                }
+                TrayIconEvent::Move { rect: r, .. } => {
+                    assert_eq!(r, rect);
+                    None
+                }
+                TrayIconEvent::Click {
+                    position,
+                    rect: r,
+                    button,
+                    button_state,
+                    ..
+                } => {
+                    assert_eq!((position, r), (inside, rect));
+                    Some(format!("{button:?} {button_state:?}"))
+                }
+                TrayIconEvent::Scroll {
+                    delta,
+                    orientation,
+                    rect: r,
+                    ..
+                } => {
+                    assert_eq!(r, rect);
+                    Some(format!("scroll {delta} {orientation:?}"))
+                }
+                TrayIconEvent::Leave { rect: r, .. } => {
+                    assert_eq!(r, rect);
+                    Some("leave".to_string())
+                }
+                event => panic!("unexpected event {event:?}"),
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                "enter",
+                "Left Down",
+                "Left Up",
+                "scroll 1 Vertical",
+                "leave"
+            ]
+        );
+
+        drop(tray);
+        assert!(manager
+            .conn
+            .get_window_attributes(window)
+            .unwrap()
+            .reply()
+            .is_err());
+    }
+}
@@ -62,6 +62,8 @@ struct RaiiIcon {
 #[derive(Clone)]
 pub(crate) struct WinIcon {
     inner: Arc<RaiiIcon>,
+    /// Kept for icons created from RGBA so overlays can be drawn on top.
+    rgba: Option<Arc<RgbaIcon>>,
 }
 
 unsafe impl Send for WinIcon {}
@@ -73,13 +75,20 @@ impl WinIcon {
 
     pub fn from_rgba(rgba: Vec<u8>, width: u32, height: u32) -> Result<Self, BadIcon> {
         let rgba_icon = RgbaIcon::from_rgba(rgba, width, height)?;
-        rgba_icon.into_windows_icon()
+        let mut icon = rgba_icon.clone().into_windows_icon()?;
+        icon.rgba = Some(Arc::new(rgba_icon));
+        Ok(icon)
+    }
+
+    pub fn to_rgba(&self) -> Option<RgbaIcon> {
+        self.rgba.as_deref().cloned()
     }
 
     pub(crate) fn from_handle(handle: HICON) -> Self {
         Self {
             #[allow(clippy::arc_with_non_send_sync)]
             inner: Arc::new(RaiiIcon { handle }),
+            rgba: None,
         }
     }
 
@@ -4,35 +4,39 @@
 
 mod icon;
 mod util;
-use std::ptr;
+use std::{ptr, time::Duration};
 
 use once_cell::sync::Lazy;
 use windows_sys::{
-    s,
+    s, w,
     Win32::{
         Foundation::{FALSE, HWND, LPARAM, LRESULT, POINT, RECT, S_OK, TRUE, WPARAM},
         UI::{
+            Input::KeyboardAndMouse::{
+                GetDoubleClickTime, GetKeyState, VIRTUAL_KEY, VK_CONTROL, VK_LWIN, VK_MENU,
+                VK_RWIN, VK_SHIFT,
+            },
             Shell::{
                 Shell_NotifyIconGetRect, Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP,
                 NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW, NOTIFYICONIDENTIFIER,
             },
             WindowsAndMessaging::{
-                CreateWindowExW, DefWindowProcW, DestroyWindow, GetCursorPos, KillTimer,
-                RegisterClassW, RegisterWindowMessageA, SendMessageW, SetForegroundWindow,
-                SetTimer, TrackPopupMenu, CREATESTRUCTW, CW_USEDEFAULT, GWL_USERDATA, HICON, HMENU,
-                TPM_BOTTOMALIGN, TPM_LEFTALIGN, WM_CREATE, WM_DESTROY, WM_LBUTTONDBLCLK,
-                WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDBLCLK, WM_MBUTTONDOWN, WM_MBUTTONUP,
-                WM_MOUSEMOVE, WM_NCCREATE, WM_RBUTTONDBLCLK, WM_RBUTTONDOWN, WM_RBUTTONUP,
-                WM_TIMER, WNDCLASSW, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW,
-                WS_EX_TRANSPARENT, WS_OVERLAPPED,
+                CreateWindowExW, DefWindowProcW, DestroyWindow, FindWindowW, GetCursorPos,
+                GetMessageTime, KillTimer, RegisterClassW, RegisterWindowMessageA, SendMessageW,
+                SetForegroundWindow, SetTimer, TrackPopupMenu, CREATESTRUCTW, CW_USEDEFAULT,
+                GWL_USERDATA, HICON, HMENU, TPM_BOTTOMALIGN, TPM_LEFTALIGN, WM_CREATE, WM_DESTROY,
+                WM_LBUTTONDBLCLK, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDBLCLK, WM_MBUTTONDOWN,
+                WM_MBUTTONUP, WM_MOUSEMOVE, WM_NCCREATE, WM_RBUTTONDBLCLK, WM_RBUTTONDOWN,
+                WM_RBUTTONUP, WM_TIMER, WNDCLASSW, WS_EX_LAYERED, WS_EX_NOACTIVATE,
+                WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT, WS_OVERLAPPED,
             },
         },
     },
 };
 
 use crate::{
-    dpi::PhysicalPosition, icon::Icon, menu, MouseButton, MouseButtonState, Rect,
-    TrayIconAttributes, TrayIconEvent, TrayIconId, COUNTER,
+    dpi::PhysicalPosition, icon::Icon, menu, EventTimestamp, Modifiers, MouseButton,
+    MouseButtonState, Rect, Tooltip, TrayIconAttributes, TrayIconEvent, TrayIconId, COUNTER,
 };
 
 pub(crate) use self::icon::WinIcon as PlatformIcon;
@@ -66,6 +70,8 @@ pub struct TrayIcon {
     hwnd: HWND,
     menu: Option<Box<dyn menu::ContextMenu>>,
     internal_id: u32,
+    icon: Option<Icon>,
+    overlay_icon: Option<Icon>,
 }
 
 impl TrayIcon {
@@ -91,7 +97,7 @@ impl TrayIcon {
                 hwnd: std::ptr::null_mut(),
                 hpopupmenu: attrs.menu.as_ref().map(|m| m.hpopupmenu() as _),
                 icon: attrs.icon.clone(),
-                tooltip: attrs.tooltip.clone(),
+                tooltip: attrs.tooltip.as_ref().map(Tooltip::to_text),
                 entered: false,
                 last_position: None,
                 menu_on_left_click: attrs.menu_on_left_click,
@@ -125,7 +131,8 @@ impl TrayIcon {
 
             let hicon = attrs.icon.as_ref().map(|i| i.inner.as_raw_handle());
 
-            if !register_tray_icon(hwnd, internal_id, &hicon, &attrs.tooltip) {
+            let tooltip = attrs.tooltip.as_ref().map(Tooltip::to_text);
+            if !register_tray_icon(hwnd, internal_id, &hicon, &tooltip) {
                 return Err(crate::Error::OsError(std::io::Error::last_os_error()));
             }
 
@@ -137,11 +144,25 @@ impl TrayIcon {
                 hwnd,
                 internal_id,
                 menu: attrs.menu,
+                icon: attrs.icon,
+                overlay_icon: None,
             })
         }
     }
 
     pub fn set_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.icon = icon;
+        self.update_icon()
+    }
+
+    pub fn set_overlay_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.overlay_icon = icon;
+        self.update_icon()
+    }
+
+    /// Updates the shell icon, with the overlay drawn on top of it.
+    fn update_icon(&mut self) -> crate::Result<()> {
+        let icon = Icon::with_overlay(self.icon.as_ref(), self.overlay_icon.as_ref());
         unsafe {
             let mut nid = NOTIFYICONDATAW {
                 uFlags: NIF_ICON,
@@ -192,7 +213,8 @@ impl TrayIcon {
         self.menu = menu;
     }
 
-    pub fn set_tooltip<S: AsRef<str>>(&mut self, tooltip: Option<S>) -> crate::Result<()> {
+    pub fn set_tooltip(&mut self, tooltip: Option<Tooltip>) -> crate::Result<()> {
+        let tooltip = tooltip.as_ref().map(Tooltip::to_text);
         unsafe {
             let mut nid = NOTIFYICONDATAW {
                 uFlags: NIF_TIP,
@@ -201,7 +223,7 @@ impl TrayIcon {
                 ..std::mem::zeroed()
             };
             if let Some(tooltip) = &tooltip {
-                let tip = util::encode_wide(tooltip.as_ref());
+                let tip = util::encode_wide(tooltip);
                 #[allow(clippy::manual_memcpy)]
                 for i in 0..tip.len().min(128) {
                     nid.szTip[i] = tip[i];
@@ -216,7 +238,7 @@ impl TrayIcon {
             SendMessageW(
                 self.hwnd,
                 WM_USER_UPDATE_TRAYTOOLTIP,
-                Box::into_raw(Box::new(tooltip.map(|t| t.as_ref().to_string()))) as _,
+                Box::into_raw(Box::new(tooltip)) as _,
                 0,
             );
         }
@@ -274,6 +296,15 @@ impl Drop for TrayIcon {
     }
 }
 
+/// The notification area is part of the taskbar, missing while Explorer is not running.
+pub fn is_tray_available() -> bool {
+    unsafe { !FindWindowW(w!("Shell_TrayWnd"), ptr::null()).is_null() }
+}
+
+pub fn double_click_interval() -> Duration {
+    Duration::from_millis(unsafe { GetDoubleClickTime() } as u64)
+}
+
 unsafe extern "system" fn tray_proc(
     hwnd: HWND,
     msg: u32,
@@ -335,6 +366,12 @@ unsafe extern "system" fn tray_proc(
                 &userdata.icon.as_ref().map(|i| i.inner.as_raw_handle()),
                 &userdata.tooltip,
             );
+
+            TrayIconEvent::send(TrayIconEvent::HostAvailabilityChanged {
+                id: userdata.id.clone(),
+                available: true,
+                timestamp: EventTimestamp::now(),
+            });
         }
         WM_USER_SHOW_MENU_ON_LEFT_CLICK => {
             userdata.menu_on_left_click = wparam != 0;
@@ -362,6 +399,8 @@ unsafe extern "system" fn tray_proc(
 
             let id = userdata.id.clone();
             let position = PhysicalPosition::new(cursor.x as f64, cursor.y as f64);
+            let modifiers = get_modifiers();
+            let timestamp = get_timestamp();
 
             let rect = match get_tray_rect(userdata.internal_id, hwnd) {
                 Some(rect) => Rect::from(rect),
@@ -373,6 +412,8 @@ unsafe extern "system" fn tray_proc(
                     id,
                     rect,
                     position,
+                    modifiers,
+                    timestamp,
                     button: MouseButton::Left,
                     button_state: MouseButtonState::Down,
                 },
@@ -380,6 +421,8 @@ unsafe extern "system" fn tray_proc(
                     id,
                     rect,
                     position,
+                    modifiers,
+                    timestamp,
                     button: MouseButton::Right,
                     button_state: MouseButtonState::Down,
                 },
@@ -387,6 +430,8 @@ unsafe extern "system" fn tray_proc(
                     id,
                     rect,
                     position,
+                    modifiers,
+                    timestamp,
                     button: MouseButton::Middle,
                     button_state: MouseButtonState::Down,
                 },
@@ -394,6 +439,8 @@ unsafe extern "system" fn tray_proc(
                     id,
                     rect,
                     position,
+                    modifiers,
+                    timestamp,
                     button: MouseButton::Left,
                     button_state: MouseButtonState::Up,
                 },
@@ -401,6 +448,8 @@ unsafe extern "system" fn tray_proc(
                     id,
                     rect,
                     position,
+                    modifiers,
+                    timestamp,
                     button: MouseButton::Right,
                     button_state: MouseButtonState::Up,
                 },
@@ -408,6 +457,8 @@ unsafe extern "system" fn tray_proc(
                     id,
                     rect,
                     position,
+                    modifiers,
+                    timestamp,
                     button: MouseButton::Middle,
                     button_state: MouseButtonState::Up,
                 },
@@ -415,23 +466,34 @@ unsafe extern "system" fn tray_proc(
                     id,
                     rect,
                     position,
+                    modifiers,
+                    timestamp,
                     button: MouseButton::Left,
                 },
                 WM_RBUTTONDBLCLK => TrayIconEvent::DoubleClick {
                     id,
                     rect,
                     position,
+                    modifiers,
+                    timestamp,
                     button: MouseButton::Right,
                 },
                 WM_MBUTTONDBLCLK => TrayIconEvent::DoubleClick {
                     id,
                     rect,
                     position,
+                    modifiers,
+                    timestamp,
                     button: MouseButton::Middle,
                 },
                 WM_MOUSEMOVE if !userdata.entered => {
                     userdata.entered = true;
-                    TrayIconEvent::Enter { id, rect, position }
+                    TrayIconEvent::Enter {
+                        id,
+                        rect,
+                        position,
+                        timestamp,
+                    }
                 }
                 WM_MOUSEMOVE if userdata.entered => {
                     // handle extra WM_MOUSEMOVE events, ignore if position hasn't changed
@@ -441,7 +503,12 @@ unsafe extern "system" fn tray_proc(
                         // Set or update existing timer, where we check if cursor left
                         SetTimer(hwnd, WM_USER_LEAVE_TIMER_ID as _, 15, Some(tray_timer_proc));
 
-                        TrayIconEvent::Move { id, rect, position }
+                        TrayIconEvent::Move {
+                            id,
+                            rect,
+                            position,
+                            timestamp,
+                        }
                     } else {
                         return 0;
                     }
@@ -484,6 +551,7 @@ unsafe extern "system" fn tray_proc(
                         id: userdata.id.clone(),
                         rect: rect.into(),
                         position,
+                        timestamp: get_timestamp(),
                     });
                 }
             }
@@ -592,6 +660,25 @@ fn get_tray_rect(id: u32, hwnd: HWND) -> Option<RECT> {
     }
 }
 
+/// Reads the modifiers from the keyboard state of the message being processed.
+#[inline]
+fn get_modifiers() -> Modifiers {
+    let pressed = |key: VIRTUAL_KEY| unsafe { GetKeyState(key as i32) } < 0;
+    Modifiers {
+        shift: pressed(VK_SHIFT),
+        control: pressed(VK_CONTROL),
+        alt: pressed(VK_MENU),
+        super_key: pressed(VK_LWIN) || pressed(VK_RWIN),
+    }
+}
+
+/// Timestamps the message being processed with the time it was posted.
+#[inline]
+fn get_timestamp() -> EventTimestamp {
+    let time = unsafe { GetMessageTime() };
+    EventTimestamp::now().with_platform_time(time as u32 as u64)
+}
+
 impl From<RECT> for Rect {
     fn from(rect: RECT) -> Self {
         Self {
@@ -0,0 +1,328 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Helpers to place a popup window next to a tray icon.
+//!
+//! ```
+//! use tray_icon::{
+//!     dpi::{PhysicalPosition, PhysicalSize},
+//!     positioner::{Alignment, Positioner},
+//!     Rect,
+//! };
+//!
+//! let monitor = Rect {
+//!     size: PhysicalSize::new(1920, 1080),
+//!     position: PhysicalPosition::new(0., 0.),
+//! };
+//! // taskbar at the bottom of the screen
+//! let work_area = Rect {
+//!     size: PhysicalSize::new(1920, 1040),
+//!     ..monitor
+//! };
+//! let tray = Rect {
+//!     size: PhysicalSize::new(24, 24),
+//!     position: PhysicalPosition::new(1700., 1048.),
+//! };
+//!
+//! let position = Positioner::new()
+//!     .with_alignment(Alignment::Center)
+//!     .with_margin(8)
+//!     .position(tray, PhysicalSize::new(300, 400), monitor, work_area);
+//! assert_eq!(position, PhysicalPosition::new(1562., 632.));
+//! ```
+
+use crate::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    Rect,
+};
+
+/// The screen edge the tray area is attached to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrayEdge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// How the popup window is aligned with the tray icon along the tray edge.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Alignment {
+    /// The left (or top) side of the window is aligned with the one of the tray icon.
+    Start,
+    /// The window is centered on the tray icon.
+    #[default]
+    Center,
+    /// The right (or bottom) side of the window is aligned with the one of the tray icon.
+    End,
+}
+
+/// Computes where to show a popup window so that it sits next to the tray icon,
+/// on the inner side of the tray edge, and stays within the work area.
+///
+/// All the rects and sizes are in physical pixels and in the same coordinate space,
+/// usually the one of [`TrayIcon::rect`](crate::TrayIcon::rect).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Positioner {
+    /// Alignment of the window with the tray icon, defaults to [`Alignment::Center`].
+    pub alignment: Alignment,
+    /// Gap between the tray icon and the window, also kept between the window
+    /// and the sides of the work area.
+    pub margin: u32,
+}
+
+impl Positioner {
+    /// Creates a positioner centering the window on the tray icon without margin.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the alignment of the window with the tray icon.
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Sets the gap kept around the window.
+    pub fn with_margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Returns the position of the top-left corner of a window of size `window`.
+    ///
+    /// - `tray`: the rect of the tray icon, a zero-sized rect at the cursor works too.
+    /// - `monitor`: the bounds of the monitor containing the tray icon.
+    /// - `work_area`: the part of `monitor` not covered by panels and taskbars.
+    pub fn position(
+        &self,
+        tray: Rect,
+        window: PhysicalSize<u32>,
+        monitor: Rect,
+        work_area: Rect,
+    ) -> PhysicalPosition<f64> {
+        let margin = self.margin as f64;
+        let (width, height) = (window.width as f64, window.height as f64);
+        let (tray_left, tray_top, tray_right, tray_bottom) = edges(&tray);
+
+        let align = |start: f64, end: f64, length: f64| match self.alignment {
+            Alignment::Start => start,
+            Alignment::Center => (start + end - length) / 2.,
+            Alignment::End => end - length,
+        };
+
+        let (x, y) = match tray_edge(tray, monitor, work_area) {
+            TrayEdge::Top => (align(tray_left, tray_right, width), tray_bottom + margin),
+            TrayEdge::Bottom => (
+                align(tray_left, tray_right, width),
+                tray_top - margin - height,
+            ),
+            TrayEdge::Left => (tray_right + margin, align(tray_top, tray_bottom, height)),
+            TrayEdge::Right => (
+                tray_left - margin - width,
+                align(tray_top, tray_bottom, height),
+            ),
+        };
+
+        let (left, top, right, bottom) = edges(&work_area);
+        PhysicalPosition::new(
+            clamp(x, left + margin, right - margin - width),
+            clamp(y, top + margin, bottom - margin - height),
+        )
+    }
+}
+
+/// Guesses the screen edge the tray area is attached to.
+///
+/// Taskbars and panels are excluded from the work area, so this is the side of
+/// the work area the tray icon lies beyond. When the tray icon is inside the work
+/// area, for example with an auto-hidden taskbar, the closest monitor edge is used.
+pub fn tray_edge(tray: Rect, monitor: Rect, work_area: Rect) -> TrayEdge {
+    let PhysicalPosition { x, y } = tray.center();
+
+    let (left, top, right, bottom) = edges(&work_area);
+    if y >= bottom {
+        return TrayEdge::Bottom;
+    }
+    if y < top {
+        return TrayEdge::Top;
+    }
+    if x >= right {
+        return TrayEdge::Right;
+    }
+    if x < left {
+        return TrayEdge::Left;
+    }
+
+    let (left, top, right, bottom) = edges(&monitor);
+    [
+        (y - top, TrayEdge::Top),
+        (bottom - y, TrayEdge::Bottom),
+        (x - left, TrayEdge::Left),
+        (right - x, TrayEdge::Right),
+    ]
+    .into_iter()
+    .min_by(|(a, _), (b, _)| a.total_cmp(b))
+    .map(|(_, edge)| edge)
+    .unwrap_or(TrayEdge::Bottom)
+}
+
+/// Returns the left, top, right and bottom coordinates of `rect`.
+fn edges(rect: &Rect) -> (f64, f64, f64, f64) {
+    let PhysicalPosition { x, y } = rect.position;
+    (
+        x,
+        y,
+        x + rect.size.width as f64,
+        y + rect.size.height as f64,
+    )
+}
+
+/// Like [`f64::clamp`] but prefers `min` when the window doesn't fit.
+fn clamp(value: f64, min: f64, max: f64) -> f64 {
+    value.min(max).max(min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: u32, height: u32) -> Rect {
+        Rect {
+            size: PhysicalSize::new(width, height),
+            position: PhysicalPosition::new(x, y),
+        }
+    }
+
+    const MONITOR: Rect = Rect {
+        size: PhysicalSize {
+            width: 1920,
+            height: 1080,
+        },
+        position: PhysicalPosition { x: 0., y: 0. },
+    };
+
+    const WINDOW: PhysicalSize<u32> = PhysicalSize {
+        width: 300,
+        height: 400,
+    };
+
+    #[test]
+    fn detects_tray_edge() {
+        let bottom = rect(0., 0., 1920, 1040);
+        let top = rect(0., 30., 1920, 1050);
+        let left = rect(48., 0., 1872, 1080);
+        let right = rect(0., 0., 1872, 1080);
+
+        assert_eq!(
+            tray_edge(rect(1890., 1050., 20, 20), MONITOR, bottom),
+            TrayEdge::Bottom
+        );
+        assert_eq!(
+            tray_edge(rect(1500., 3., 24, 24), MONITOR, top),
+            TrayEdge::Top
+        );
+        assert_eq!(
+            tray_edge(rect(12., 1000., 24, 24), MONITOR, left),
+            TrayEdge::Left
+        );
+        assert_eq!(
+            tray_edge(rect(1884., 1000., 24, 24), MONITOR, right),
+            TrayEdge::Right
+        );
+        // auto-hidden taskbar, falls back to the closest monitor edge
+        assert_eq!(
+            tray_edge(rect(1500., 1060., 0, 0), MONITOR, MONITOR),
+            TrayEdge::Bottom
+        );
+        assert_eq!(
+            tray_edge(rect(5., 500., 0, 0), MONITOR, MONITOR),
+            TrayEdge::Left
+        );
+    }
+
+    #[test]
+    fn places_window_next_to_tray() {
+        let tray = rect(1000., 1050., 20, 20);
+        let work_area = rect(0., 0., 1920, 1040);
+        let positioner = Positioner::new().with_margin(10);
+
+        assert_eq!(
+            positioner.position(tray, WINDOW, MONITOR, work_area),
+            PhysicalPosition::new(860., 630.)
+        );
+        assert_eq!(
+            positioner
+                .with_alignment(Alignment::Start)
+                .position(tray, WINDOW, MONITOR, work_area),
+            PhysicalPosition::new(1000., 630.)
+        );
+        assert_eq!(
+            positioner
+                .with_alignment(Alignment::End)
+                .position(tray, WINDOW, MONITOR, work_area),
+            PhysicalPosition::new(720., 630.)
+        );
+
+        let tray = rect(1500., 3., 24, 24);
+        let work_area = rect(0., 30., 1920, 1050);
+        assert_eq!(
+            positioner.position(tray, WINDOW, MONITOR, work_area),
+            PhysicalPosition::new(1362., 40.)
+        );
+
+        let tray = rect(12., 500., 24, 24);
+        let work_area = rect(48., 0., 1872, 1080);
+        assert_eq!(
+            positioner.position(tray, WINDOW, MONITOR, work_area),
+            PhysicalPosition::new(58., 312.)
+        );
+
+        let tray = rect(1884., 500., 24, 24);
+        let work_area = rect(0., 0., 1872, 1080);
+        assert_eq!(
+            positioner
+                .with_alignment(Alignment::Start)
+                .position(tray, WINDOW, MONITOR, work_area),
+            PhysicalPosition::new(1562., 500.)
+        );
+    }
+
+    #[test]
+    fn clamps_window_to_work_area() {
+        let work_area = rect(0., 0., 1920, 1040);
+        let positioner = Positioner::new().with_margin(8);
+
+        // near the right end of the taskbar
+        assert_eq!(
+            positioner.position(rect(1890., 1050., 20, 20), WINDOW, MONITOR, work_area),
+            PhysicalPosition::new(1612., 632.)
+        );
+        // near the left end of the taskbar
+        assert_eq!(
+            positioner.position(rect(10., 1050., 20, 20), WINDOW, MONITOR, work_area),
+            PhysicalPosition::new(8., 632.)
+        );
+        // larger than the work area, keeps the top-left corner visible
+        assert_eq!(
+            positioner.position(
+                rect(1000., 1050., 20, 20),
+                PhysicalSize::new(3000, 2000),
+                MONITOR,
+                work_area
+            ),
+            PhysicalPosition::new(8., 8.)
+        );
+        // monitor on the right of the primary one
+        let monitor = rect(1920., 0., 1280, 1024);
+        let work_area = rect(1920., 0., 1280, 984);
+        assert_eq!(
+            positioner.position(rect(3180., 994., 20, 20), WINDOW, monitor, work_area),
+            PhysicalPosition::new(2892., 576.)
+        );
+    }
+}
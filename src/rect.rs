@@ -0,0 +1,222 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use crate::dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
+
+/// Describes a rectangle including position (x - y axis) and size.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub size: PhysicalSize<u32>,
+    pub position: PhysicalPosition<f64>,
+}
+
+impl Default for Rect {
+    fn default() -> Self {
+        Self {
+            size: PhysicalSize::new(0, 0),
+            position: PhysicalPosition::new(0., 0.),
+        }
+    }
+}
+
+impl Rect {
+    /// Returns the x coordinate of the right side of the rectangle.
+    fn right(&self) -> f64 {
+        self.position.x + self.size.width as f64
+    }
+
+    /// Returns the y coordinate of the bottom side of the rectangle.
+    fn bottom(&self) -> f64 {
+        self.position.y + self.size.height as f64
+    }
+
+    /// Returns `true` if `position` is inside the rectangle.
+    ///
+    /// The left and top sides are inclusive while the right and bottom sides are not,
+    /// so an empty rectangle contains nothing.
+    pub fn contains(&self, position: PhysicalPosition<f64>) -> bool {
+        position.x >= self.position.x
+            && position.x < self.right()
+            && position.y >= self.position.y
+            && position.y < self.bottom()
+    }
+
+    /// Returns the center of the rectangle.
+    pub fn center(&self) -> PhysicalPosition<f64> {
+        PhysicalPosition::new(
+            self.position.x + self.size.width as f64 / 2.,
+            self.position.y + self.size.height as f64 / 2.,
+        )
+    }
+
+    /// Returns `true` if both rectangles overlap, rectangles only sharing a side don't.
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.position.x < other.right()
+            && other.position.x < self.right()
+            && self.position.y < other.bottom()
+            && other.position.y < self.bottom()
+    }
+
+    /// Returns the smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.position.x.min(other.position.x);
+        let y = self.position.y.min(other.position.y);
+        let width = self.right().max(other.right()) - x;
+        let height = self.bottom().max(other.bottom()) - y;
+        Rect {
+            size: PhysicalSize::new(width.round() as u32, height.round() as u32),
+            position: PhysicalPosition::new(x, y),
+        }
+    }
+
+    /// Converts the rectangle to logical pixels using `scale_factor`.
+    pub fn to_logical(&self, scale_factor: f64) -> LogicalRect {
+        LogicalRect {
+            size: self.size.to_logical(scale_factor),
+            position: self.position.to_logical(scale_factor),
+        }
+    }
+
+    /// Converts a rectangle in logical pixels to physical pixels using `scale_factor`.
+    pub fn from_logical(rect: LogicalRect, scale_factor: f64) -> Rect {
+        Rect {
+            size: rect.size.to_physical(scale_factor),
+            position: rect.position.to_physical(scale_factor),
+        }
+    }
+}
+
+/// Converts a `(position, size)` pair, as returned by the `outer_position` and
+/// `outer_size` methods of winit and tao windows, or plain `((x, y), (width, height))` tuples.
+impl<P, S> From<(P, S)> for Rect
+where
+    P: Into<PhysicalPosition<f64>>,
+    S: Into<PhysicalSize<u32>>,
+{
+    fn from((position, size): (P, S)) -> Self {
+        Self {
+            size: size.into(),
+            position: position.into(),
+        }
+    }
+}
+
+impl From<Rect> for (PhysicalPosition<f64>, PhysicalSize<u32>) {
+    fn from(rect: Rect) -> Self {
+        (rect.position, rect.size)
+    }
+}
+
+/// A [`Rect`] in logical pixels, see [`Rect::to_logical`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogicalRect {
+    pub size: LogicalSize<f64>,
+    pub position: LogicalPosition<f64>,
+}
+
+impl Default for LogicalRect {
+    fn default() -> Self {
+        Self {
+            size: LogicalSize::new(0., 0.),
+            position: LogicalPosition::new(0., 0.),
+        }
+    }
+}
+
+impl LogicalRect {
+    /// Converts the rectangle to physical pixels using `scale_factor`.
+    pub fn to_physical(&self, scale_factor: f64) -> Rect {
+        Rect::from_logical(*self, scale_factor)
+    }
+}
+
+impl<P, S> From<(P, S)> for LogicalRect
+where
+    P: Into<LogicalPosition<f64>>,
+    S: Into<LogicalSize<f64>>,
+{
+    fn from((position, size): (P, S)) -> Self {
+        Self {
+            size: size.into(),
+            position: position.into(),
+        }
+    }
+}
+
+impl From<LogicalRect> for (LogicalPosition<f64>, LogicalSize<f64>) {
+    fn from(rect: LogicalRect) -> Self {
+        (rect.position, rect.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: u32, height: u32) -> Rect {
+        Rect::from(((x, y), (width, height)))
+    }
+
+    #[test]
+    fn hit_tests() {
+        let r = rect(10., 20., 30, 40);
+        assert!(r.contains(PhysicalPosition::new(10., 20.)));
+        assert!(r.contains(PhysicalPosition::new(39.5, 59.5)));
+        assert!(!r.contains(PhysicalPosition::new(40., 30.)));
+        assert!(!r.contains(PhysicalPosition::new(20., 60.)));
+        assert!(!r.contains(PhysicalPosition::new(9., 30.)));
+        assert!(!rect(10., 20., 0, 0).contains(PhysicalPosition::new(10., 20.)));
+        assert_eq!(r.center(), PhysicalPosition::new(25., 40.));
+    }
+
+    #[test]
+    fn intersects_and_unions() {
+        let a = rect(0., 0., 10, 10);
+        let b = rect(5., 5., 10, 10);
+        let c = rect(10., 0., 10, 10);
+
+        assert!(a.intersects(&b) && b.intersects(&a));
+        assert!(b.intersects(&c));
+        assert!(!a.intersects(&c));
+        assert!(!a.intersects(&rect(-20., 30., 5, 5)));
+
+        assert_eq!(a.union(&b), rect(0., 0., 15, 15));
+        assert_eq!(a.union(&rect(-20., 30., 5, 5)), rect(-20., 0., 30, 35));
+    }
+
+    #[test]
+    fn converts_to_logical_and_back() {
+        let r = rect(30., -15., 48, 24);
+        let logical = r.to_logical(1.5);
+        assert_eq!(logical, LogicalRect::from(((20., -10.), (32., 16.))));
+        assert_eq!(Rect::from_logical(logical, 1.5), r);
+        assert_eq!(logical.to_physical(1.5), r);
+
+        let (position, size): (PhysicalPosition<f64>, PhysicalSize<u32>) = r.into();
+        assert_eq!(Rect::from((position, size)), r);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_logical_rect() {
+        let rect = LogicalRect::from(((20., -10.), (32., 16.)));
+        let value = serde_json::to_value(rect).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "size": {
+                    "width": 32.0,
+                    "height": 16.0,
+                },
+                "position": {
+                    "x": 20.0,
+                    "y": -10.0,
+                },
+            })
+        );
+        assert_eq!(serde_json::from_value::<LogicalRect>(value).unwrap(), rect);
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::HashMap,
+    sync::{RwLock, Weak},
+};
+#[cfg(feature = "async")]
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crossbeam_channel::{unbounded, Sender};
+use once_cell::sync::Lazy;
+
+use crate::{
+    broadcast::Queue, counter::Counter, TrayIconEvent, TrayIconEventHandler, TrayIconEventReceiver,
+    TrayIconId,
+};
+
+static OWNERS: Counter = Counter::new();
+static KEYS: Counter = Counter::new();
+/// Subscribers by tray icon, `None` holds the ones receiving the events of all tray icons.
+static SUBSCRIBERS: Lazy<RwLock<HashMap<Option<TrayIconId>, Vec<Subscriber>>>> =
+    Lazy::new(Default::default);
+/// Owner of the global subscribers, never dropped.
+const GLOBAL_OWNER: u32 = 0;
+
+#[derive(Clone)]
+struct Subscriber {
+    owner: u32,
+    key: u32,
+    sink: Sink,
+}
+
+#[derive(Clone)]
+enum Sink {
+    Handler(TrayIconEventHandler),
+    Channel(Sender<TrayIconEvent>),
+    #[cfg(feature = "async")]
+    Stream(Sender<TrayIconEvent>, Arc<Mutex<Option<Waker>>>),
+    Broadcast(Weak<Queue>),
+}
+
+impl Sink {
+    /// Drops the sink, a stream ends once its sender is dropped so the task
+    /// polling it is woken up to notice.
+    fn close(self) {
+        #[cfg(feature = "async")]
+        if let Sink::Stream(sender, waker) = self {
+            drop(sender);
+            if let Some(waker) = waker.lock().unwrap().take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// The subscriptions made through a tray icon, removed when dropped.
+pub(crate) struct Subscriptions {
+    owner: u32,
+    id: TrayIconId,
+}
+
+impl Subscriptions {
+    pub fn new(id: TrayIconId) -> Self {
+        Self {
+            owner: OWNERS.next(),
+            id,
+        }
+    }
+
+    pub fn on_event(&self, handler: TrayIconEventHandler) {
+        subscribe(Some(&self.id), self.owner, Sink::Handler(handler));
+    }
+
+    pub fn event_receiver(&self) -> TrayIconEventReceiver {
+        let (sender, receiver) = unbounded();
+        subscribe(Some(&self.id), self.owner, Sink::Channel(sender));
+        receiver
+    }
+
+    #[cfg(feature = "async")]
+    pub fn event_stream(&self) -> TrayIconEventStream {
+        TrayIconEventStream::subscribe(Some(&self.id), self.owner)
+    }
+}
+
+fn subscribe(id: Option<&TrayIconId>, owner: u32, sink: Sink) {
+    SUBSCRIBERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(id.cloned())
+        .or_default()
+        .push(Subscriber {
+            owner,
+            key: KEYS.next(),
+            sink,
+        });
+}
+
+/// Sends the events of all tray icons to `queue` until it is dropped.
+pub(crate) fn subscribe_broadcast(queue: Weak<Queue>) {
+    subscribe(None, GLOBAL_OWNER, Sink::Broadcast(queue));
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        let key = Some(self.id.clone());
+        let mut removed = Vec::new();
+        {
+            let mut subscribers = SUBSCRIBERS.write().unwrap_or_else(|e| e.into_inner());
+            if let Some(icon_subscribers) = subscribers.get_mut(&key) {
+                let (owned, kept) = std::mem::take(icon_subscribers)
+                    .into_iter()
+                    .partition(|s| s.owner == self.owner);
+                *icon_subscribers = kept;
+                removed = owned;
+                if icon_subscribers.is_empty() {
+                    subscribers.remove(&key);
+                }
+            }
+        }
+
+        for subscriber in removed {
+            subscriber.sink.close();
+        }
+    }
+}
+
+/// Sends `event` to the subscribers of the tray icon it belongs to and to the
+/// subscribers of all tray icons.
+pub(crate) fn dispatch(event: &TrayIconEvent) {
+    dispatch_to(&Some(event.id().clone()), event);
+    dispatch_to(&None, event);
+}
+
+/// Like the global handler, subscribers are called without holding the lock,
+/// channels and queues that were dropped are removed afterwards.
+fn dispatch_to(key: &Option<TrayIconId>, event: &TrayIconEvent) {
+    let subscribers = match SUBSCRIBERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(key)
+    {
+        Some(subscribers) => subscribers.clone(),
+        None => return,
+    };
+
+    let mut disconnected = Vec::new();
+    for subscriber in subscribers {
+        let connected = match subscriber.sink {
+            Sink::Handler(handler) => {
+                handler(event.clone());
+                true
+            }
+            Sink::Channel(sender) => sender.send(event.clone()).is_ok(),
+            #[cfg(feature = "async")]
+            Sink::Stream(sender, waker) => {
+                let connected = sender.send(event.clone()).is_ok();
+                // a stream woken after its tray icon is dropped must see the end
+                drop(sender);
+                if let Some(waker) = waker.lock().unwrap().take() {
+                    waker.wake();
+                }
+                connected
+            }
+            Sink::Broadcast(queue) => match queue.upgrade() {
+                Some(queue) => {
+                    queue.push(event.clone());
+                    true
+                }
+                None => false,
+            },
+        };
+        if !connected {
+            disconnected.push(subscriber.key);
+        }
+    }
+
+    if !disconnected.is_empty() {
+        let mut subscribers = SUBSCRIBERS.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(icon_subscribers) = subscribers.get_mut(key) {
+            icon_subscribers.retain(|s| !disconnected.contains(&s.key));
+        }
+    }
+}
+
+/// A [`Stream`](futures_core::Stream) of tray events, see [`TrayIconEvent::stream`]
+/// and [`TrayIcon::event_stream`](crate::TrayIcon::event_stream).
+///
+/// Events are queued from the moment the stream is created, the stream can be
+/// polled from any async runtime.
+#[cfg(feature = "async")]
+pub struct TrayIconEventStream {
+    receiver: TrayIconEventReceiver,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+#[cfg(feature = "async")]
+impl TrayIconEventStream {
+    fn subscribe(id: Option<&TrayIconId>, owner: u32) -> Self {
+        let (sender, receiver) = unbounded();
+        let waker = Arc::new(Mutex::new(None));
+        subscribe(id, owner, Sink::Stream(sender, waker.clone()));
+        Self { receiver, waker }
+    }
+
+    pub(crate) fn global() -> Self {
+        Self::subscribe(None, GLOBAL_OWNER)
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for TrayIconEventStream {
+    type Item = TrayIconEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        use crossbeam_channel::TryRecvError;
+
+        match self.receiver.try_recv() {
+            Ok(event) => return Poll::Ready(Some(event)),
+            Err(TryRecvError::Disconnected) => return Poll::Ready(None),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+
+        // an event may have been sent before the waker was stored
+        match self.receiver.try_recv() {
+            Ok(event) => Poll::Ready(Some(event)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::EventTimestamp;
+    #[cfg(feature = "async")]
+    use futures_core::Stream;
+
+    fn event(id: &str) -> TrayIconEvent {
+        TrayIconEvent::HostAvailabilityChanged {
+            id: TrayIconId::new(id),
+            available: true,
+            timestamp: EventTimestamp::now(),
+        }
+    }
+
+    #[test]
+    fn delivers_only_the_icon_events() {
+        let first = Subscriptions::new(TrayIconId::new("subscription-first"));
+        let second = Subscriptions::new(TrayIconId::new("subscription-second"));
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        first.on_event(Arc::new(move |event| {
+            assert_eq!(event.id(), "subscription-first");
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        let first_receiver = first.event_receiver();
+        let second_receiver = second.event_receiver();
+
+        dispatch(&event("subscription-first"));
+        dispatch(&event("subscription-first"));
+        dispatch(&event("subscription-second"));
+        dispatch(&event("subscription-other"));
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(first_receiver.try_iter().count(), 2);
+        assert_eq!(
+            second_receiver
+                .try_iter()
+                .map(|event| event.id().clone())
+                .collect::<Vec<_>>(),
+            [TrayIconId::new("subscription-second")]
+        );
+    }
+
+    #[test]
+    fn drops_subscriptions() {
+        let id = TrayIconId::new("subscription-drop");
+        let subscriptions = Subscriptions::new(id.clone());
+
+        // dropped receivers are forgotten on the next event
+        drop(subscriptions.event_receiver());
+        let receiver = subscriptions.event_receiver();
+        dispatch(&event("subscription-drop"));
+        assert_eq!(SUBSCRIBERS.read().unwrap()[&Some(id.clone())].len(), 1);
+        assert_eq!(receiver.try_iter().count(), 1);
+
+        drop(subscriptions);
+        assert!(!SUBSCRIBERS.read().unwrap().contains_key(&Some(id)));
+        assert!(receiver.recv().is_err());
+    }
+
+    #[cfg(feature = "async")]
+    struct CountingWaker(AtomicUsize);
+
+    #[cfg(feature = "async")]
+    impl std::task::Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn streams_events() {
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let subscriptions = Subscriptions::new(TrayIconId::new("subscription-stream"));
+        let mut stream = subscriptions.event_stream();
+        let mut global = TrayIconEventStream::global();
+
+        assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+        dispatch(&event("subscription-stream"));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+        let next = |stream: &mut TrayIconEventStream, cx: &mut Context<'_>| match Pin::new(stream)
+            .poll_next(cx)
+        {
+            Poll::Ready(Some(event)) => Some(event.id().clone()),
+            Poll::Ready(None) => None,
+            Poll::Pending => panic!("no event"),
+        };
+        assert_eq!(
+            next(&mut stream, &mut cx),
+            Some(TrayIconId::new("subscription-stream"))
+        );
+
+        // the global stream also sees the events of other tray icons
+        dispatch(&event("subscription-stream-other"));
+        let mut ids = Vec::new();
+        while let Poll::Ready(Some(event)) = Pin::new(&mut global).poll_next(&mut cx) {
+            ids.push(event.id().clone());
+        }
+        assert!(ids.contains(&TrayIconId::new("subscription-stream")));
+        assert!(ids.contains(&TrayIconId::new("subscription-stream-other")));
+
+        // ends with the tray icon
+        drop(subscriptions);
+        assert_eq!(next(&mut stream, &mut cx), None);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn wakes_streams_when_dropped() {
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let subscriptions = Subscriptions::new(TrayIconId::new("subscription-stream-drop"));
+        let mut stream = subscriptions.event_stream();
+        assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+
+        drop(subscriptions);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert!(matches!(
+            Pin::new(&mut stream).poll_next(&mut cx),
+            Poll::Ready(None)
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+
+/// Reference point of the serialized [`EventTimestamp::instant`].
+pub(crate) static EPOCH: Lazy<Instant> = Lazy::new(Instant::now);
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+/// When a [`TrayIconEvent`](crate::TrayIconEvent) happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct EventTimestamp {
+    /// When the event was received from the platform.
+    ///
+    /// Serialized as the number of microseconds since the first timestamp created
+    /// by the process, so it is only meaningful within the same process.
+    #[cfg_attr(feature = "serde", serde(with = "instant"))]
+    pub instant: Instant,
+    /// Time of the event given by the platform, in milliseconds.
+    ///
+    /// ## Platform-specific:
+    ///
+    /// - **Windows**: Time since the system started, from `GetMessageTime`. Wraps around after 49.7 days.
+    /// - **macOS**: Time since the system started, from `NSEvent.timestamp`.
+    /// - **Linux**: Server time of the X event with the `xembed` fallback, wraps around after 49.7 days.
+    ///   `None` with tray hosts that don't forward it.
+    pub platform_time: Option<u64>,
+    /// Number of the event among the events sent by this process, starting at 1.
+    ///
+    /// Assigned when the event is sent, so a greater number means a more recent event.
+    pub sequence: u64,
+}
+
+impl EventTimestamp {
+    /// Creates a timestamp for an event received now, without platform time.
+    pub fn now() -> Self {
+        Lazy::force(&EPOCH);
+        Self {
+            instant: Instant::now(),
+            platform_time: None,
+            sequence: 0,
+        }
+    }
+
+    /// Sets the time of the event given by the platform, in milliseconds.
+    pub fn with_platform_time(mut self, platform_time: u64) -> Self {
+        self.platform_time = Some(platform_time);
+        self
+    }
+
+    /// Returns the time elapsed since the event was received.
+    pub fn elapsed(&self) -> Duration {
+        self.instant.elapsed()
+    }
+
+    pub(crate) fn assign_sequence(&mut self) {
+        self.sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "serde")]
+mod instant {
+    use std::time::{Duration, Instant};
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::EPOCH;
+
+    pub fn serialize<S: Serializer>(instant: &Instant, serializer: S) -> Result<S::Ok, S::Error> {
+        let micros = instant.saturating_duration_since(*EPOCH).as_micros();
+        serializer.serialize_u64(micros.try_into().unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Instant, D::Error> {
+        let micros = u64::deserialize(deserializer)?;
+        Ok(*EPOCH + Duration::from_micros(micros))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assigns_increasing_sequences() {
+        let mut first = EventTimestamp::now();
+        let mut second = EventTimestamp::now().with_platform_time(42);
+        first.assign_sequence();
+        second.assign_sequence();
+
+        assert!(first.sequence > 0);
+        assert!(second.sequence > first.sequence);
+        assert!(second.instant >= first.instant);
+        assert_eq!(first.platform_time, None);
+        assert_eq!(second.platform_time, Some(42));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_relative_to_epoch() {
+        let timestamp = EventTimestamp {
+            instant: *EPOCH + Duration::from_micros(1500),
+            platform_time: Some(42),
+            sequence: 7,
+        };
+
+        let value = serde_json::to_value(timestamp).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "instant": 1500,
+                "platformTime": 42,
+                "sequence": 7,
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<EventTimestamp>(value).unwrap(),
+            timestamp
+        );
+    }
+}
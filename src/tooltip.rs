@@ -0,0 +1,162 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::borrow::Cow;
+
+use crate::Icon;
+
+/// A tray icon tooltip made of a title, a body and an optional icon.
+///
+/// ## Platform-specific:
+///
+/// - **Linux:** Shown as is by the tray host, `body` may contain the simple markup
+///   described in the [StatusNotifierItem spec](https://www.freedesktop.org/wiki/Specifications/StatusNotifierItem/Markup/).
+///   Requires the `sni` feature.
+/// - **Windows / macOS:** The title and body are joined on separate lines with
+///   the markup removed, the icon is ignored.
+#[derive(Debug, Clone, Default)]
+pub struct Tooltip {
+    /// Heading of the tooltip, usually shown in bold.
+    pub title: String,
+    /// Longer description shown below the title.
+    pub body: String,
+    /// Icon shown next to the text.
+    pub icon: Option<Icon>,
+}
+
+impl Tooltip {
+    /// Creates a new tooltip with only a title.
+    pub fn new<S: AsRef<str>>(title: S) -> Self {
+        Self {
+            title: title.as_ref().to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the body of the tooltip.
+    pub fn with_body<S: AsRef<str>>(mut self, body: S) -> Self {
+        self.body = body.as_ref().to_string();
+        self
+    }
+
+    /// Sets the icon of the tooltip.
+    pub fn with_icon(mut self, icon: Icon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Plain text version for platforms that only support a single string.
+    #[allow(unused)]
+    pub(crate) fn to_text(&self) -> String {
+        let body = strip_markup(&self.body);
+        [self.title.as_str(), body.as_str()]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl From<&str> for Tooltip {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for Tooltip {
+    fn from(value: String) -> Self {
+        Self {
+            title: value,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&String> for Tooltip {
+    fn from(value: &String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&&str> for Tooltip {
+    fn from(value: &&str) -> Self {
+        Self::new(*value)
+    }
+}
+
+impl From<Box<str>> for Tooltip {
+    fn from(value: Box<str>) -> Self {
+        value.into_string().into()
+    }
+}
+
+impl From<Cow<'_, str>> for Tooltip {
+    fn from(value: Cow<'_, str>) -> Self {
+        value.into_owned().into()
+    }
+}
+
+/// Removes tags and decodes the entities allowed in the tooltip markup.
+fn strip_markup(markup: &str) -> String {
+    let mut text = String::with_capacity(markup.len());
+    let mut rest = markup;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => {
+                let tag = &rest[start + 1..start + end];
+                if tag.trim_end_matches('/').trim().eq_ignore_ascii_case("br") {
+                    text.push('\n');
+                }
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    text.push_str(rest);
+
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_text_joins_and_strips_markup() {
+        assert_eq!(Tooltip::from("title").to_text(), "title");
+        assert_eq!(
+            Tooltip::new("Volume")
+                .with_body("<b>42%</b><br/>Tom &amp; Jerry &lt;3")
+                .to_text(),
+            "Volume\n42%\nTom & Jerry <3"
+        );
+        assert_eq!(Tooltip::default().with_body("body").to_text(), "body");
+    }
+
+    #[test]
+    fn converts_from_strings() {
+        fn title(tooltip: impl Into<Tooltip>) -> String {
+            tooltip.into().title
+        }
+
+        let strs = ["b"];
+        let strings = [String::from("d")];
+
+        assert_eq!(title("a"), "a");
+        assert_eq!(title(strs.first().unwrap()), "b");
+        assert_eq!(title(String::from("c")), "c");
+        assert_eq!(title(strings.first().unwrap()), "d");
+        assert_eq!(title(Box::<str>::from("e")), "e");
+        assert_eq!(title(Cow::Borrowed("f")), "f");
+        assert_eq!(title(Cow::<str>::Owned("g".into())), "g");
+    }
+}